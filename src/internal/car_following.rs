@@ -0,0 +1,150 @@
+use super::constants::*;
+use super::vehicles::Vehicle;
+
+/// Gipps car-following model.
+///
+/// A follower drives at the lower of two speeds: the one it would accelerate
+/// to on a free road, and the fastest one from which it can still stop
+/// `jam_gap` behind its leader if the leader brakes as hard as it can, after
+/// reacting for `reaction_time`. Both controllers use it for every vehicle
+/// that is not already committed to crossing, so lanes form queues that close
+/// up when the leader stops and open out again when it pulls away.
+#[derive(Clone, Copy, Debug)]
+pub struct Gipps {
+    /// Seconds before the follower responds to its leader
+    pub reaction_time: f32,
+    /// Bumper-to-bumper gap kept to a stopped leader
+    pub jam_gap: f32,
+}
+
+impl Gipps {
+    /// Velocity for the next step of a vehicle that wants to drive at
+    /// `desired`, given the gap to its leader and the leader's velocity and
+    /// hardest braking
+    pub fn velocity(&self, vehicle: &Vehicle, desired: f32, leader: Option<(f32, f32, f32)>) -> f32 {
+        let free = self.free_velocity(vehicle, desired);
+        match leader {
+            Some((gap, leader_velocity, leader_braking)) => {
+                free.min(self.safe_velocity(vehicle, gap, leader_velocity, leader_braking))
+            }
+            None => free,
+        }
+    }
+
+    /// Speed reached after one reaction time of accelerating towards `desired`,
+    /// easing off as the vehicle gets close to it
+    fn free_velocity(&self, vehicle: &Vehicle, desired: f32) -> f32 {
+        let velocity = vehicle.velocity;
+        if desired <= velocity || desired <= 0.0 {
+            return desired.max(0.0);
+        }
+
+        let ratio = velocity / desired;
+        let gain = 2.5 * vehicle.max_acceleration * self.reaction_time * (1.0 - ratio) * (0.025 + ratio).sqrt();
+        (velocity + gain).min(desired)
+    }
+
+    /// Fastest speed that still lets the vehicle stop `jam_gap` behind a leader
+    /// `gap` ahead travelling at `leader_velocity`, assuming the leader brakes as
+    /// hard as it can with `leader_braking`
+    fn safe_velocity(&self, vehicle: &Vehicle, gap: f32, leader_velocity: f32, leader_braking: f32) -> f32 {
+        let braking = vehicle.max_deceleration;
+        let reaction = braking * self.reaction_time;
+        let room = 2.0 * (gap - self.jam_gap) - vehicle.velocity * self.reaction_time;
+        let leader_stop = braking * leader_velocity * leader_velocity / leader_braking;
+        let discriminant = reaction * reaction + braking * room + leader_stop;
+        (discriminant.max(0.0).sqrt() - reaction).max(0.0)
+    }
+}
+
+impl Default for Gipps {
+    fn default() -> Self {
+        Gipps {
+            reaction_time: REACTION_TIME,
+            jam_gap: SAFE_GAP,
+        }
+    }
+}
+
+/// Gap to, velocity of and hardest braking of the nearest vehicle ahead of
+/// `vehicle` on the same route or in the same lane
+pub fn leader(vehicle: &Vehicle, vehicles: &[Vehicle]) -> Option<(f32, f32, f32)> {
+    vehicles
+        .iter()
+        .filter_map(|other| vehicle.gap_to(other).map(|gap| (gap, other.velocity, other.max_deceleration)))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::vehicles::{Direction, SpeedTier, TurnDirection};
+
+    fn vehicle(velocity: f32) -> Vehicle {
+        let mut vehicle = Vehicle::new(Direction::North, 4, TurnDirection::Straight);
+        vehicle.velocity = velocity;
+        vehicle
+    }
+
+    #[test]
+    fn free_road_speeds_up_to_the_desired_velocity() {
+        let gipps = Gipps::default();
+        let desired = SpeedTier::Fast.velocity();
+        let mut follower = vehicle(0.0);
+        let mut previous = 0.0;
+        for _ in 0..2000 {
+            let velocity = gipps.velocity(&follower, desired, None);
+            assert!(velocity >= previous && velocity <= desired);
+            previous = velocity;
+            follower.move_vehicle(velocity, SIMULATION_DT);
+        }
+        assert!(desired - follower.velocity < 1.0, "{}", follower.velocity);
+    }
+
+    #[test]
+    fn slows_down_for_a_desired_velocity_below_the_current_one() {
+        let gipps = Gipps::default();
+        assert_eq!(gipps.velocity(&vehicle(100.0), 40.0, None), 40.0);
+        assert_eq!(gipps.velocity(&vehicle(100.0), -5.0, None), 0.0);
+    }
+
+    #[test]
+    fn stays_put_behind_a_stopped_leader_at_the_jam_gap() {
+        let gipps = Gipps::default();
+        let stopped = vehicle(0.0);
+        let leader = Some((gipps.jam_gap, 0.0, stopped.max_deceleration));
+        assert!(gipps.velocity(&stopped, SpeedTier::Fast.velocity(), leader) < 1e-3);
+    }
+
+    #[test]
+    fn stops_behind_a_stopped_leader_without_touching_it() {
+        let gipps = Gipps::default();
+        let mut leader = vehicle(0.0);
+        leader.distance_travelled = 300.0;
+        let mut follower = vehicle(SpeedTier::Fast.velocity());
+        for _ in 0..2000 {
+            let ahead = follower.gap_to(&leader).map(|gap| (gap, leader.velocity, leader.max_deceleration));
+            let velocity = gipps.velocity(&follower, SpeedTier::Fast.velocity(), ahead);
+            follower.move_vehicle(velocity, SIMULATION_DT);
+            assert!(follower.gap_to(&leader).unwrap() > 0.0);
+        }
+        assert!(follower.velocity < 1.0, "{}", follower.velocity);
+        assert!(follower.gap_to(&leader).unwrap() >= gipps.jam_gap - 1.0);
+    }
+
+    #[test]
+    fn keeps_clear_of_a_leader_braking_as_hard_as_it_can() {
+        let gipps = Gipps::default();
+        let cruise = SpeedTier::Fast.velocity();
+        let mut leader = vehicle(cruise);
+        leader.distance_travelled = 100.0;
+        let mut follower = vehicle(cruise);
+        for _ in 0..2000 {
+            let ahead = follower.gap_to(&leader).map(|gap| (gap, leader.velocity, leader.max_deceleration));
+            let velocity = gipps.velocity(&follower, cruise, ahead);
+            follower.move_vehicle(velocity, SIMULATION_DT);
+            leader.move_vehicle(0.0, SIMULATION_DT);
+            assert!(follower.gap_to(&leader).unwrap() > 0.0);
+        }
+    }
+}
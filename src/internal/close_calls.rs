@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::config::config;
+use super::constants::*;
+use super::geometry::polygon_distance;
+use super::vehicles::Vehicle;
+
+/// Minimum distance two vehicles should keep, growing with speed; the
+/// `[close_calls]` table of the config
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafetyDistance {
+    /// Distance when stationary
+    pub base: f32,
+    /// Extra distance per pixel/second of the faster vehicle's velocity
+    pub per_velocity: f32,
+}
+
+impl SafetyDistance {
+    pub fn at(&self, velocity: f32) -> f32 {
+        self.base + self.per_velocity * velocity
+    }
+}
+
+impl Default for SafetyDistance {
+    fn default() -> Self {
+        SafetyDistance {
+            base: 5.0,
+            per_velocity: 1.0 / 30.0,
+        }
+    }
+}
+
+/// Two vehicles getting closer than the safety distance
+#[derive(Clone, Debug)]
+pub struct CloseCall {
+    /// Simulation time in seconds
+    pub time: f64,
+    /// Midpoint between the two vehicles
+    pub pos_x: f32,
+    pub pos_y: f32,
+    pub vehicles: (u32, u32),
+}
+
+/// Watches every pair of vehicles in or near the intersection and records a
+/// close call the first time a pair breaks the safety distance
+pub struct CloseCallDetector {
+    safety_distance: SafetyDistance,
+    /// Pairs already recorded, lowest id first
+    recorded: HashSet<(u32, u32)>,
+    pub events: Vec<CloseCall>,
+}
+
+impl CloseCallDetector {
+    pub fn new(safety_distance: SafetyDistance) -> Self {
+        CloseCallDetector {
+            safety_distance,
+            recorded: HashSet::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn safety_distance(&self) -> SafetyDistance {
+        self.safety_distance
+    }
+
+    /// Check all pairs at simulation time `time` and return the close calls that are new
+    pub fn update(&mut self, vehicles: &[Vehicle], time: f64) -> &[CloseCall] {
+        let first_new = self.events.len();
+
+        // Pairs can't recur once a vehicle is gone
+        self.recorded.retain(|&(a, b)| {
+            vehicles.iter().any(|vehicle| vehicle.id == a)
+                && vehicles.iter().any(|vehicle| vehicle.id == b)
+        });
+
+        let nearby: Vec<&Vehicle> = vehicles
+            .iter()
+            .filter(|vehicle| is_near_intersection(vehicle))
+            .collect();
+
+        for (i, vehicle) in nearby.iter().enumerate() {
+            for other in &nearby[i + 1..] {
+                let pair = (vehicle.id.min(other.id), vehicle.id.max(other.id));
+                if self.recorded.contains(&pair) {
+                    continue;
+                }
+
+                let limit = self.safety_distance.at(vehicle.velocity.max(other.velocity));
+                if distance_between(vehicle, other) < limit {
+                    self.recorded.insert(pair);
+                    self.events.push(CloseCall {
+                        time,
+                        pos_x: (vehicle.pos_x + other.pos_x) / 2.0,
+                        pos_y: (vehicle.pos_y + other.pos_y) / 2.0,
+                        vehicles: pair,
+                    });
+                }
+            }
+        }
+
+        &self.events[first_new..]
+    }
+}
+
+/// Whether the vehicle is in the box or within `CLOSE_CALL_RANGE` of it
+fn is_near_intersection(vehicle: &Vehicle) -> bool {
+    let (left, top, right, bottom) = vehicle.bounds();
+    let (west, east, north, south) = config().intersection();
+    right > west - CLOSE_CALL_RANGE
+        && left < east + CLOSE_CALL_RANGE
+        && bottom > north - CLOSE_CALL_RANGE
+        && top < south + CLOSE_CALL_RANGE
+}
+
+/// Shortest distance between the bodies of two vehicles, zero if they touch
+fn distance_between(a: &Vehicle, b: &Vehicle) -> f32 {
+    polygon_distance(&a.footprint(0.0), &b.footprint(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::vehicles::{Direction, SpeedTier, TurnDirection};
+
+    /// A northbound vehicle `offset` pixels right of the centre of the box
+    fn vehicle(id: u32, offset: f32) -> Vehicle {
+        let mut vehicle = Vehicle::new(Direction::North, 4, TurnDirection::Straight);
+        vehicle.id = id;
+        vehicle.pos_x = WINDOW_WIDTH as f32 / 2.0 + offset;
+        vehicle.pos_y = WINDOW_HEIGHT as f32 / 2.0;
+        vehicle
+    }
+
+    #[test]
+    fn records_a_pair_once_while_both_are_around() {
+        let mut detector = CloseCallDetector::new(SafetyDistance::default());
+        let (a, b) = (vehicle(3, 0.0), vehicle(1, 25.0));
+        let close_calls = detector.update(&[a.clone(), b.clone()], 1.0);
+        assert_eq!(close_calls.len(), 1);
+        assert_eq!(close_calls[0].vehicles, (1, 3));
+
+        // Apart and close again is still the same close call
+        assert!(detector.update(&[a.clone(), vehicle(1, 200.0)], 2.0).is_empty());
+        assert!(detector.update(&[a.clone(), b.clone()], 3.0).is_empty());
+        assert_eq!(detector.events.len(), 1);
+
+        // Another vehicle just as close is a new one
+        assert_eq!(detector.update(&[a, b, vehicle(2, -25.0)], 4.0).len(), 1);
+    }
+
+    #[test]
+    fn safety_distance_grows_with_speed() {
+        let mut detector = CloseCallDetector::new(SafetyDistance::default());
+        // 5 px apart: too close at medium speed, fine when stopped
+        let (mut a, mut b) = (vehicle(1, 0.0), vehicle(2, 25.0));
+        a.velocity = 0.0;
+        b.velocity = 0.0;
+        assert!(detector.update(&[a.clone(), b.clone()], 1.0).is_empty());
+        b.velocity = SpeedTier::Medium.velocity();
+        assert_eq!(detector.update(&[a, b], 2.0).len(), 1);
+    }
+}
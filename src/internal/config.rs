@@ -0,0 +1,421 @@
+use std::sync::OnceLock;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::close_calls::SafetyDistance;
+use super::constants::*;
+use super::routes::{standard_routes, validate_routes, LaneRoutes};
+use super::vehicles::{Direction, VehicleType};
+
+static CONFIG: OnceLock<RoadConfig> = OnceLock::new();
+
+/// The configuration installed at startup, or the defaults if there is none
+pub fn config() -> &'static RoadConfig {
+    CONFIG.get_or_init(RoadConfig::default)
+}
+
+/// Road layout, vehicle size, speeds and spawning, read from a TOML file given
+/// with `--config`. Every table and key is optional and falls back to the
+/// defaults below; all road geometry is derived from these values.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoadConfig {
+    pub roads: Roads,
+    pub vehicle: VehicleSize,
+    pub speeds: SpeedLimits,
+    pub spawning: Spawning,
+    pub platoons: Platoons,
+    pub close_calls: SafetyDistance,
+    /// Route table; left out or empty, `standard_routes` is used
+    #[serde(default)]
+    pub routes: Vec<LaneRoutes>,
+}
+
+impl Default for RoadConfig {
+    fn default() -> Self {
+        let mut config = RoadConfig {
+            roads: Roads::default(),
+            vehicle: VehicleSize::default(),
+            speeds: SpeedLimits::default(),
+            spawning: Spawning::default(),
+            platoons: Platoons::default(),
+            close_calls: SafetyDistance::default(),
+            routes: Vec::new(),
+        };
+        config.routes = standard_routes(&config);
+        config
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Roads {
+    /// Lanes each way on every road (1-5)
+    pub lanes_per_direction: i32,
+    pub lane_width: f32,
+    /// Width of the east-west road; lanes are centred on it and any room left
+    /// over is shoulder. Defaults to exactly fit the lanes.
+    pub horizontal_width: Option<f32>,
+    /// Width of the north-south road, as above
+    pub vertical_width: Option<f32>,
+}
+
+impl Default for Roads {
+    fn default() -> Self {
+        Roads {
+            lanes_per_direction: 3,
+            lane_width: 83.0,
+            horizontal_width: None,
+            vertical_width: None,
+        }
+    }
+}
+
+/// Size of a car; the other vehicle types are sized relative to it
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VehicleSize {
+    /// Along the direction of travel
+    pub length: f32,
+    pub width: f32,
+}
+
+impl Default for VehicleSize {
+    fn default() -> Self {
+        VehicleSize {
+            length: 30.0,
+            width: 20.0,
+        }
+    }
+}
+
+/// Speed tiers in pixels per second
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeedLimits {
+    pub slow: f32,
+    pub medium: f32,
+    pub fast: f32,
+}
+
+impl Default for SpeedLimits {
+    fn default() -> Self {
+        SpeedLimits {
+            slow: 60.0,
+            medium: 120.0,
+            fast: 180.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Spawning {
+    /// Seconds between random spawn attempts
+    pub interval: f32,
+    /// Chance of a random spawn on each attempt
+    pub chance: f64,
+    /// No random spawns beyond this many vehicles
+    pub max_vehicles: usize,
+    /// Room needed ahead of a newly spawned vehicle
+    pub gap: f32,
+    /// Vehicle types spawned, at random and by the user
+    pub mix: SpawnMix,
+}
+
+/// Relative chances of each vehicle type being spawned
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnMix {
+    pub car: f32,
+    pub truck: f32,
+    pub bus: f32,
+    pub motorcycle: f32,
+}
+
+impl Default for SpawnMix {
+    fn default() -> Self {
+        SpawnMix {
+            car: 6.0,
+            truck: 1.5,
+            bus: 1.0,
+            motorcycle: 1.5,
+        }
+    }
+}
+
+impl SpawnMix {
+    fn weights(&self) -> [(VehicleType, f32); 4] {
+        [
+            (VehicleType::Car, self.car),
+            (VehicleType::Truck, self.truck),
+            (VehicleType::Bus, self.bus),
+            (VehicleType::Motorcycle, self.motorcycle),
+        ]
+    }
+
+    /// Draw a vehicle type in proportion to the weights
+    pub fn pick(&self, rng: &mut impl Rng) -> VehicleType {
+        let weights = self.weights();
+        let total: f32 = weights.iter().map(|&(_, weight)| weight).sum();
+        let mut draw = rng.gen_range(0.0..total);
+        for (vehicle_type, weight) in weights {
+            if draw < weight {
+                return vehicle_type;
+            }
+            draw -= weight;
+        }
+        // Only reached through rounding
+        weights
+            .iter()
+            .rev()
+            .find(|&&(_, weight)| weight > 0.0)
+            .map_or(VehicleType::Car, |&(vehicle_type, _)| vehicle_type)
+    }
+}
+
+impl Default for Spawning {
+    fn default() -> Self {
+        Spawning {
+            interval: 1.0,
+            chance: 0.3,
+            max_vehicles: 20,
+            gap: 40.0,
+            mix: SpawnMix::default(),
+        }
+    }
+}
+
+/// Batches formed by the platoon scheduler
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Platoons {
+    /// Most vehicles let through the box in one batch
+    pub max_batch: usize,
+    /// Batches in a row an approach lane with vehicles queued can be passed
+    /// over before it leads the next batch, after any lanes passed over longer
+    pub max_skipped: u32,
+}
+
+impl Default for Platoons {
+    fn default() -> Self {
+        Platoons {
+            max_batch: 6,
+            max_skipped: 3,
+        }
+    }
+}
+
+impl RoadConfig {
+    /// Read and validate a configuration file
+    pub fn load(path: &str) -> Result<RoadConfig, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        Self::from_toml(&text).map_err(|e| format!("Invalid config {}: {}", path, e))
+    }
+
+    /// Parse and validate a configuration
+    pub fn from_toml(text: &str) -> Result<RoadConfig, String> {
+        let mut config: RoadConfig = toml::from_str(text).map_err(|e| e.to_string())?;
+        if config.routes.is_empty() {
+            config.routes = standard_routes(&config);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("road configuration is always representable as TOML")
+    }
+
+    /// Make this the configuration returned by `config()`. Must happen before
+    /// anything reads the configuration.
+    pub fn install(self) {
+        if CONFIG.set(self).is_err() {
+            panic!("Road configuration installed after it was first used");
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let roads = &self.roads;
+        if !(1..=5).contains(&roads.lanes_per_direction) {
+            return Err(format!(
+                "roads.lanes_per_direction must be 1-5, got {}",
+                roads.lanes_per_direction
+            ));
+        }
+        if roads.lane_width <= 0.0 {
+            return Err("roads.lane_width must be positive".to_string());
+        }
+        let lanes_width = self.lanes_width();
+        for (name, width, window) in [
+            ("roads.horizontal_width", self.horizontal_road_width(), WINDOW_HEIGHT),
+            ("roads.vertical_width", self.vertical_road_width(), WINDOW_WIDTH),
+        ] {
+            if width < lanes_width {
+                return Err(format!("{} must be at least {} to fit the lanes", name, lanes_width));
+            }
+            if width >= window as f32 {
+                return Err(format!("{} must be less than the window ({})", name, window));
+            }
+        }
+
+        let vehicle = &self.vehicle;
+        if vehicle.length <= 0.0 || vehicle.width <= 0.0 {
+            return Err("vehicle.length and vehicle.width must be positive".to_string());
+        }
+        let (_, widest) = VehicleType::largest_body(self);
+        if widest >= roads.lane_width {
+            return Err(format!(
+                "vehicle.width must leave the widest vehicle type ({} px) narrower than roads.lane_width",
+                widest
+            ));
+        }
+
+        let speeds = &self.speeds;
+        if !(0.0 < speeds.slow && speeds.slow <= speeds.medium && speeds.medium <= speeds.fast) {
+            return Err("speeds must be positive with slow <= medium <= fast".to_string());
+        }
+
+        let spawning = &self.spawning;
+        if spawning.interval <= 0.0 {
+            return Err("spawning.interval must be positive".to_string());
+        }
+        if !(0.0..=1.0).contains(&spawning.chance) {
+            return Err("spawning.chance must be between 0 and 1".to_string());
+        }
+        let weights = spawning.mix.weights();
+        if weights.iter().any(|&(_, weight)| weight < 0.0) || weights.iter().all(|&(_, weight)| weight <= 0.0) {
+            return Err("spawning.mix weights must not be negative, and at least one must be positive".to_string());
+        }
+
+        if self.platoons.max_batch == 0 {
+            return Err("platoons.max_batch must be at least 1".to_string());
+        }
+        if self.close_calls.base < 0.0 || self.close_calls.per_velocity < 0.0 {
+            return Err("close_calls.base and close_calls.per_velocity must not be negative".to_string());
+        }
+
+        validate_routes(self)
+    }
+
+    /// Lanes across a whole road, both directions
+    pub fn total_lanes(&self) -> i32 {
+        self.roads.lanes_per_direction * 2
+    }
+
+    /// Width taken up by the lanes of one road
+    fn lanes_width(&self) -> f32 {
+        self.total_lanes() as f32 * self.roads.lane_width
+    }
+
+    pub fn horizontal_road_width(&self) -> f32 {
+        self.roads.horizontal_width.unwrap_or_else(|| self.lanes_width())
+    }
+
+    pub fn vertical_road_width(&self) -> f32 {
+        self.roads.vertical_width.unwrap_or_else(|| self.lanes_width())
+    }
+
+    /// Edges of the intersection box as (west, east, north, south)
+    pub fn intersection(&self) -> (f32, f32, f32, f32) {
+        let (center_x, center_y) = (WINDOW_WIDTH as f32 / 2.0, WINDOW_HEIGHT as f32 / 2.0);
+        let (half_vertical, half_horizontal) = (self.vertical_road_width() / 2.0, self.horizontal_road_width() / 2.0);
+        (
+            center_x - half_vertical,
+            center_x + half_vertical,
+            center_y - half_horizontal,
+            center_y + half_horizontal,
+        )
+    }
+
+    /// X coordinate of the left edge of a lane on the vertical road (lanes
+    /// numbered left to right); `total_lanes()` gives the right edge of the road's lanes
+    pub fn vertical_lane_edge(&self, lane: i32) -> f32 {
+        (WINDOW_WIDTH as f32 - self.lanes_width()) / 2.0 + lane as f32 * self.roads.lane_width
+    }
+
+    /// Y coordinate of the top edge of a lane on the horizontal road (lanes
+    /// numbered top to bottom)
+    pub fn horizontal_lane_edge(&self, lane: i32) -> f32 {
+        (WINDOW_HEIGHT as f32 - self.lanes_width()) / 2.0 + lane as f32 * self.roads.lane_width
+    }
+
+    /// First lane used by traffic travelling in `direction`; its lanes run on
+    /// from there for `lanes_per_direction`. Traffic drives on the right, so
+    /// northbound and eastbound traffic use the second half of their road.
+    pub fn inbound_start(&self, direction: Direction) -> i32 {
+        match direction {
+            Direction::North | Direction::East => self.roads.lanes_per_direction,
+            Direction::South | Direction::West => 0,
+        }
+    }
+
+    /// Lane of traffic travelling in `direction` that is `offset` lanes out
+    /// from the centre line of the road
+    pub fn lane_from_centre(&self, direction: Direction, offset: i32) -> i32 {
+        match direction {
+            Direction::North | Direction::East => self.roads.lanes_per_direction + offset,
+            Direction::South | Direction::West => self.roads.lanes_per_direction - 1 - offset,
+        }
+    }
+
+    /// How many lanes out from the centre line `lane` is, for traffic
+    /// travelling in `direction`
+    pub fn centre_offset(&self, direction: Direction, lane: i32) -> i32 {
+        match direction {
+            Direction::North | Direction::East => lane - self.roads.lanes_per_direction,
+            Direction::South | Direction::West => self.roads.lanes_per_direction - 1 - lane,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_config_is_the_default() {
+        let config = RoadConfig::from_toml("").unwrap();
+        assert_eq!(config.to_toml(), RoadConfig::default().to_toml());
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let text = "[roads]\nlanes_per_direction = 2\n\n[spawning.mix]\ntruck = 3.0\n";
+        let config = RoadConfig::from_toml(text).unwrap();
+        let again = RoadConfig::from_toml(&config.to_toml()).unwrap();
+        assert_eq!(again.to_toml(), config.to_toml());
+        assert_eq!(again.roads.lanes_per_direction, 2);
+        assert_eq!(again.routes.len(), 8);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(RoadConfig::from_toml("[roads]\nlanes = 2\n").is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        for (text, expected) in [
+            ("[roads]\nlanes_per_direction = 6", "lanes_per_direction must be 1-5"),
+            ("[roads]\nlane_width = 20", "narrower than roads.lane_width"),
+            ("[roads]\nvertical_width = 100", "vertical_width must be at least"),
+            ("[speeds]\nslow = 200", "slow <= medium <= fast"),
+            ("[spawning]\nchance = 1.5", "chance must be between 0 and 1"),
+            ("[spawning.mix]\ncar = 0\ntruck = 0\nbus = 0\nmotorcycle = 0", "at least one must be positive"),
+            ("[platoons]\nmax_batch = 0", "max_batch must be at least 1"),
+            ("[close_calls]\nbase = -1", "must not be negative"),
+        ] {
+            let error = RoadConfig::from_toml(text).unwrap_err();
+            assert!(error.contains(expected), "{}: {}", text, error);
+        }
+    }
+
+    #[test]
+    fn checks_the_route_table() {
+        let text = "[[routes]]\ndirection = \"North\"\nlane = 1\nstraight = true\n";
+        let error = RoadConfig::from_toml(text).unwrap_err();
+        assert!(error.contains("not a lane for North traffic"), "{}", error);
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use super::config::config;
+use super::constants::*;
+use super::geometry::{oriented_rect, polygons_overlap};
+use super::paths::{Path, Pose};
+use super::vehicles::{Route, VehicleType};
+
+static CONFLICT_MATRIX: OnceLock<ConflictMatrix> = OnceLock::new();
+
+/// Conflicts between every pair of routes in the route table, built the first
+/// time it is asked for; the configuration must be installed by then
+pub fn conflict_matrix() -> &'static ConflictMatrix {
+    CONFLICT_MATRIX.get_or_init(ConflictMatrix::build)
+}
+
+/// How two routes meet. Distances are along each route's path from where it
+/// enters the screen, first route first.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Conflict {
+    /// Vehicles on the two routes can never touch
+    None,
+    /// The paths cross; `at` is where they come closest
+    Cross { at: (f32, f32) },
+    /// The paths end on the same lane; `at` is where vehicles on them first
+    /// touch
+    Merge { at: (f32, f32) },
+    /// The paths start in the same lane; `at` is where vehicles on them stop
+    /// touching
+    Diverge { at: (f32, f32) },
+}
+
+impl Conflict {
+    /// The same conflict seen from the other route
+    fn swapped(self) -> Conflict {
+        let swap = |(a, b)| (b, a);
+        match self {
+            Conflict::None => Conflict::None,
+            Conflict::Cross { at } => Conflict::Cross { at: swap(at) },
+            Conflict::Merge { at } => Conflict::Merge { at: swap(at) },
+            Conflict::Diverge { at } => Conflict::Diverge { at: swap(at) },
+        }
+    }
+}
+
+/// Geometric conflicts between every pair of routes, found by sliding a body
+/// as long and as wide as the largest vehicle type along each path through
+/// the box and checking where the bodies on two paths overlap.
+pub struct ConflictMatrix {
+    routes: Vec<Route>,
+    index: HashMap<Route, usize>,
+    /// Row-major, `routes.len()` squared
+    conflicts: Vec<Conflict>,
+}
+
+impl ConflictMatrix {
+    pub fn build() -> Self {
+        let routes = Route::all();
+        let index = routes.iter().enumerate().map(|(i, &route)| (route, i)).collect();
+        let samples: Vec<Vec<Sample>> = routes.iter().map(|&route| samples(route)).collect();
+
+        let count = routes.len();
+        let mut conflicts = vec![Conflict::None; count * count];
+        for a in 0..count {
+            for b in a + 1..count {
+                let conflict = classify(routes[a], routes[b], &samples[a], &samples[b]);
+                conflicts[a * count + b] = conflict;
+                conflicts[b * count + a] = conflict.swapped();
+            }
+        }
+
+        ConflictMatrix {
+            routes,
+            index,
+            conflicts,
+        }
+    }
+
+    /// How `a` meets `b`; a route never conflicts with itself
+    pub fn get(&self, a: Route, b: Route) -> Conflict {
+        match (self.index.get(&a), self.index.get(&b)) {
+            (Some(&a), Some(&b)) => self.conflicts[a * self.routes.len() + b],
+            _ => Conflict::None,
+        }
+    }
+
+    /// Whether vehicles on `a` and `b` could touch anywhere
+    pub fn conflicts(&self, a: Route, b: Route) -> bool {
+        self.get(a, b) != Conflict::None
+    }
+}
+
+/// A vehicle body at a point on a path
+struct Sample {
+    distance: f32,
+    pose: Pose,
+    body: [(f32, f32); 4],
+}
+
+/// Bodies along the part of `route`'s path where it could meet another
+/// route: in the box or within a vehicle's length of it
+fn samples(route: Route) -> Vec<Sample> {
+    let config = config();
+    let (length, width) = VehicleType::largest_body(config);
+    let (half_length, half_width) = (length / 2.0, width / 2.0);
+    let reach = libm::hypotf(length, width);
+    let (west, east, north, south) = config.intersection();
+    let near_box = |pose: &Pose| {
+        pose.x > west - reach && pose.x < east + reach && pose.y > north - reach && pose.y < south + reach
+    };
+
+    let path = Path::for_route(route);
+    let run = WINDOW_WIDTH.max(WINDOW_HEIGHT) as f32 * 2.0;
+    let mut samples = Vec::new();
+    let mut distance = 0.0;
+    while distance < run {
+        let pose = path.pose_at(distance);
+        if near_box(&pose) {
+            let body = oriented_rect(
+                pose.x,
+                pose.y,
+                pose.heading,
+                half_length + CONFLICT_MARGIN,
+                half_width + CONFLICT_MARGIN,
+            );
+            samples.push(Sample { distance, pose, body });
+        } else if !samples.is_empty() {
+            break;
+        }
+        distance += CONFLICT_STEP;
+    }
+    samples
+}
+
+fn classify(a: Route, b: Route, samples_a: &[Sample], samples_b: &[Sample]) -> Conflict {
+    let (length, width) = VehicleType::largest_body(config());
+    let reach = libm::hypotf(length, width) + 2.0 * CONFLICT_MARGIN;
+    let mut touching = Vec::new();
+    for sample_a in samples_a {
+        for sample_b in samples_b {
+            let gap = libm::hypotf(sample_a.pose.x - sample_b.pose.x, sample_a.pose.y - sample_b.pose.y);
+            if gap < reach && polygons_overlap(&sample_a.body, &sample_b.body) {
+                touching.push((sample_a.distance, sample_b.distance, gap));
+            }
+        }
+    }
+    if touching.is_empty() {
+        return Conflict::None;
+    }
+
+    let by_total = |x: &&(f32, f32, f32), y: &&(f32, f32, f32)| (x.0 + x.1).total_cmp(&(y.0 + y.1));
+    if a.direction == b.direction && a.lane == b.lane {
+        let &(at_a, at_b, _) = touching.iter().max_by(by_total).expect("not empty");
+        Conflict::Diverge { at: (at_a, at_b) }
+    } else if a.exit_direction() == b.exit_direction() && a.target_lane() == b.target_lane() {
+        let &(at_a, at_b, _) = touching.iter().min_by(by_total).expect("not empty");
+        Conflict::Merge { at: (at_a, at_b) }
+    } else {
+        let &(at_a, at_b, _) = touching.iter().min_by(|x, y| x.2.total_cmp(&y.2)).expect("not empty");
+        Conflict::Cross { at: (at_a, at_b) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::vehicles::{Direction, TurnDirection};
+
+    /// The route of the default table travelling `direction` and turning
+    /// `turn_direction`
+    fn route(direction: Direction, turn_direction: TurnDirection) -> Route {
+        Route::all()
+            .into_iter()
+            .find(|route| route.direction == direction && route.turn_direction == turn_direction)
+            .unwrap()
+    }
+
+    #[test]
+    fn is_symmetric() {
+        let matrix = conflict_matrix();
+        for a in Route::all() {
+            assert_eq!(matrix.get(a, a), Conflict::None);
+            for b in Route::all() {
+                assert_eq!(matrix.get(a, b), matrix.get(b, a).swapped());
+            }
+        }
+    }
+
+    #[test]
+    fn crossing_roads_cross() {
+        let matrix = conflict_matrix();
+        let north = route(Direction::North, TurnDirection::Straight);
+        let east = route(Direction::East, TurnDirection::Straight);
+        assert!(matches!(matrix.get(north, east), Conflict::Cross { .. }));
+    }
+
+    #[test]
+    fn oncoming_traffic_only_meets_left_turns() {
+        let matrix = conflict_matrix();
+        let north = route(Direction::North, TurnDirection::Straight);
+        let south = route(Direction::South, TurnDirection::Straight);
+        let south_left = route(Direction::South, TurnDirection::Left);
+        assert!(!matrix.conflicts(north, south));
+        assert!(matrix.conflicts(north, south_left));
+    }
+
+    #[test]
+    fn right_turns_meet_nothing() {
+        // With the default table they run in the outermost lanes from corner
+        // to corner
+        let matrix = conflict_matrix();
+        for right in Route::all().into_iter().filter(|route| route.turn_direction == TurnDirection::Right) {
+            assert!(Route::all().into_iter().all(|other| !matrix.conflicts(right, other)), "{:?}", right);
+        }
+    }
+}
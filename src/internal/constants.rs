@@ -1,40 +1,62 @@
 pub const WINDOW_WIDTH: u32 = 1280;
 pub const WINDOW_HEIGHT: u32 = 720;
-
-// Road widths adjusted to be divisible by 6 (number of lanes)
-pub const ROAD_HORIZONTAL_WIDTH: u32 = 498;  // 83 pixels per lane
-pub const ROAD_VERTICAL_WIDTH: u32 = 498;    // 83 pixels per lane
-
-// Intersection boundaries
-pub const INTERSECTION_BOUNDARY_NORTH: u32 = (WINDOW_HEIGHT / 2) - (ROAD_VERTICAL_WIDTH / 2);
-pub const INTERSECTION_BOUNDARY_SOUTH: u32 = (WINDOW_HEIGHT / 2) + (ROAD_VERTICAL_WIDTH / 2);
-pub const INTERSECTION_BOUNDARY_WEST: u32 = (WINDOW_WIDTH / 2) - (ROAD_HORIZONTAL_WIDTH / 2);
-pub const INTERSECTION_BOUNDARY_EAST: u32 = (WINDOW_WIDTH / 2) + (ROAD_HORIZONTAL_WIDTH / 2);
-
-// Lane organization (3 lanes per direction)
-pub const LANES_PER_SIDE: i32 = 3;
-pub const TOTAL_LANES: i32 = LANES_PER_SIDE * 2;
-
-// North road (left to right: 0-5)
-pub const NORTH_OUTBOUND_START: i32 = 0;     // Left 3 lanes (0,1,2)
-pub const NORTH_INBOUND_START: i32 = 3;      // Right 3 lanes (3,4,5)
-
-// South road (left to right: 0-5)
-pub const SOUTH_INBOUND_START: i32 = 0;      // Left 3 lanes (0,1,2)
-pub const SOUTH_OUTBOUND_START: i32 = 3;     // Right 3 lanes (3,4,5)
-
-// East road (top to bottom: 0-5)
-pub const EAST_OUTBOUND_START: i32 = 0;      // Top 3 lanes (0,1,2)
-pub const EAST_INBOUND_START: i32 = 3;       // Bottom 3 lanes (3,4,5)
-
-// West road (top to bottom: 0-5)
-pub const WEST_INBOUND_START: i32 = 0;       // Top 3 lanes (0,1,2)
-pub const WEST_OUTBOUND_START: i32 = 3;      // Bottom 3 lanes (3,4,5)
-
-// Vehicle constants
-pub const VEHICLE_WIDTH: u32 = 30;
-pub const VEHICLE_HEIGHT: u32 = 20;
-pub const VEHICLE_SPEED: i32 = 2;
-
-// Lane width
-pub const LANE_WIDTH: i32 = (ROAD_HORIZONTAL_WIDTH as i32) / TOTAL_LANES;
+pub const TICKS_PER_SECOND: u32 = 60;
+pub const SIMULATION_DT: f32 = 1.0 / TICKS_PER_SECOND as f32;
+pub const MAX_FRAME_TIME: f32 = 0.25;  // Longest real frame fed to the simulation, in seconds
+pub const MAX_FRAME_RATE: u32 = 120;   // Frames drawn per second at most, if the display does not pace them
+pub const SIMULATION_SPEEDS: [f32; 4] = [0.5, 1.0, 2.0, 8.0];  // Selected with keys 1-4
+
+pub const STATS_WINDOW_WIDTH: u32 = 420;
+pub const STATS_WINDOW_HEIGHT: u32 = 330;
+
+// Heads-up display
+pub const HUD_MARGIN: i32 = 10;
+pub const HUD_WIDTH: u32 = 210;
+pub const HUD_LINE_HEIGHT: u32 = 20;
+pub const HUD_FPS_INTERVAL: f32 = 0.5; // Seconds of frames averaged for each FPS reading
+
+pub const TEXT_CACHE_LIMIT: usize = 512;  // Rendered strings kept before the text cache is emptied
+// Top-down car models, drawn facing east and rotated to each vehicle's heading
+pub const CAR_SPRITES: [&str; 3] = [
+    "assets/cars/car_1.png",
+    "assets/cars/car_2.png",
+    "assets/cars/car_3.png",
+];
+
+// Vehicle kinematics, in pixels per second (sizes, speed tiers and spawning
+// come from the road configuration)
+pub const MAX_ACCELERATION: f32 = 180.0;
+pub const MAX_DECELERATION: f32 = 540.0;
+
+// Intersection management
+pub const SAFE_GAP: f32 = 12.0;      // Minimum bumper-to-bumper gap in a lane
+pub const REACTION_TIME: f32 = 0.1;  // Car-following reaction time, in seconds
+pub const STOP_MARGIN: f32 = 2.0;    // How far short of the box a waiting vehicle stops
+pub const DECISION_MARGIN: f32 = 8.0; // Look-ahead beyond braking distance for entry decisions
+pub const WAITING_VELOCITY: f32 = 5.0; // Slower than this before the box counts as waiting
+pub const TILE_SIZE: i32 = 10;       // Resolution used to compare route footprints
+pub const CONFLICT_STEP: f32 = 2.0;  // Spacing of the bodies sampled along each route for the conflict matrix
+pub const CONFLICT_MARGIN: f32 = 2.0; // Clearance added around those bodies
+pub const RESERVATION_DISTANCE: f32 = 100.0;  // How close to the box a vehicle asks for tiles
+pub const RESERVATION_MARGIN: f32 = 4.0;      // Extra clearance reserved around each vehicle
+pub const RESERVATION_EXIT: f32 = 60.0;       // How far past the box a reservation extends
+pub const REQUEST_RETRY: u64 = 6;             // Ticks a denied vehicle waits before asking for a reservation again
+pub const SIGNAL_GREEN: f32 = 8.0;            // Green time per approach of the virtual traffic light
+pub const SIGNAL_MIN_GREEN: f32 = 2.0;        // Longest-queue-first greens last at least this long,
+pub const SIGNAL_MAX_GREEN: f32 = 12.0;       // and at most this long
+pub const SIGNAL_MAX_RED: f32 = 30.0;         // An approach kept red this long with vehicles queued goes next
+pub const SIGNAL_ALL_RED: f32 = 1.0;          // Shortest all-red between greens
+pub const PLATOON_RANGE: f32 = 150.0;        // How close to the box a vehicle joins the platoon scheduler's queues
+pub const PLATOON_GAP: f32 = 40.0;           // Widest bumper-to-bumper gap within a platoon
+pub const EMERGENCY_RANGE: f32 = 250.0;      // How close to the box an emergency vehicle takes right of way
+pub const EMERGENCY_FLASH: f32 = 0.25;       // Seconds between swaps of an emergency vehicle's lights
+
+// Close calls
+pub const CLOSE_CALL_RANGE: f32 = 60.0; // How far from the box pairs are checked
+
+// Trajectory export
+pub const EXPORT_EVERY: u64 = 6;         // Ticks between exported samples unless --export-every is given
+
+// Replay
+pub const TIMELINE_HEIGHT: u32 = 10;     // Height of the replay timeline along the bottom of the window
+pub const SCRUB_SECONDS: f32 = 5.0;      // How far the left and right arrow keys jump in a replay
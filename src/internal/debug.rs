@@ -0,0 +1,176 @@
+use sdl2::{
+    keyboard::Keycode,
+    pixels::Color,
+    rect::{Point, Rect},
+    render::{BlendMode, Canvas},
+    video::Window,
+};
+
+use super::constants::*;
+use super::geometry::oriented_rect;
+use super::lanes::get_intersection_boundaries;
+use super::simulation::Simulation;
+use super::text::TextCache;
+use super::vehicles::Vehicle;
+
+/// Colours told apart by vehicle id, for the tiles each vehicle holds
+const HOLDER_COLORS: [(u8, u8, u8); 6] = [
+    (230, 80, 80),
+    (80, 200, 90),
+    (90, 140, 240),
+    (230, 200, 60),
+    (200, 90, 220),
+    (70, 210, 210),
+];
+
+/// Diagnostic drawing over the simulation. F1 shows or hides the overlay and
+/// F2-F5 toggle its layers:
+///
+/// - F2: each vehicle's id, speed and the path ahead of it
+/// - F3: each vehicle's safety-distance envelope
+/// - F4: the intersection tiles or conflict zones each vehicle holds
+/// - F5: the intersection boundaries
+pub struct DebugOverlay {
+    pub visible: bool,
+    pub labels: bool,
+    pub envelopes: bool,
+    pub held_tiles: bool,
+    pub boundaries: bool,
+}
+
+impl DebugOverlay {
+    pub fn new() -> Self {
+        DebugOverlay {
+            visible: false,
+            labels: true,
+            envelopes: true,
+            held_tiles: true,
+            boundaries: true,
+        }
+    }
+
+    /// Toggle the overlay or one of its layers; false if `keycode` is not one
+    /// of the overlay's keys
+    pub fn handle_key(&mut self, keycode: Keycode) -> bool {
+        let layer = match keycode {
+            Keycode::F1 => &mut self.visible,
+            Keycode::F2 => &mut self.labels,
+            Keycode::F3 => &mut self.envelopes,
+            Keycode::F4 => &mut self.held_tiles,
+            Keycode::F5 => &mut self.boundaries,
+            _ => return false,
+        };
+        *layer = !*layer;
+        true
+    }
+
+    /// Draw the visible layers, with vehicles `alpha` of the way from their
+    /// previous physics step to their current one, as `draw_simulation` does
+    pub fn draw(&self, canvas: &mut Canvas<Window>, text: &mut TextCache, simulation: &Simulation, alpha: f32) {
+        if !self.visible {
+            return;
+        }
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        if self.held_tiles {
+            draw_held_tiles(canvas, simulation);
+        }
+        if self.boundaries {
+            let (west, east, north, south) = get_intersection_boundaries();
+            canvas.set_draw_color(Color::RGB(255, 0, 255));
+            canvas
+                .draw_rect(Rect::new(west, north, (east - west) as u32, (south - north) as u32))
+                .unwrap_or_else(|e| eprintln!("Error drawing intersection boundaries: {}", e));
+        }
+        if self.envelopes {
+            let safety_distance = simulation.close_calls.safety_distance();
+            canvas.set_draw_color(Color::RGBA(255, 160, 0, 200));
+            for vehicle in &simulation.vehicles {
+                // Two envelopes touch where the vehicles are a safety distance apart
+                let margin = safety_distance.at(vehicle.velocity) / 2.0;
+                let (pos_x, pos_y, heading) = vehicle.interpolated_pose(alpha);
+                let envelope = oriented_rect(
+                    pos_x,
+                    pos_y,
+                    heading,
+                    vehicle.length / 2.0 + margin,
+                    vehicle.width / 2.0 + margin,
+                );
+                draw_outline(canvas, &envelope);
+            }
+        }
+        if self.labels {
+            for vehicle in &simulation.vehicles {
+                draw_path(canvas, vehicle, alpha);
+            }
+            for vehicle in &simulation.vehicles {
+                draw_label(canvas, text, vehicle, alpha);
+            }
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+    }
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn draw_held_tiles(canvas: &mut Canvas<Window>, simulation: &Simulation) {
+    for (id, (tile_x, tile_y)) in simulation.policy().held_tiles() {
+        let (r, g, b) = HOLDER_COLORS[id as usize % HOLDER_COLORS.len()];
+        canvas.set_draw_color(Color::RGBA(r, g, b, 90));
+        let tile = Rect::new(tile_x * TILE_SIZE, tile_y * TILE_SIZE, TILE_SIZE as u32, TILE_SIZE as u32);
+        canvas
+            .fill_rect(tile)
+            .unwrap_or_else(|e| eprintln!("Error drawing held tile: {}", e));
+    }
+}
+
+fn draw_outline(canvas: &mut Canvas<Window>, polygon: &[(f32, f32); 4]) {
+    let points: Vec<Point> = polygon
+        .iter()
+        .chain(polygon.first())
+        .map(|&(x, y)| Point::new(x.round() as i32, y.round() as i32))
+        .collect();
+    canvas
+        .draw_lines(points.as_slice())
+        .unwrap_or_else(|e| eprintln!("Error drawing safety envelope: {}", e));
+}
+
+/// The rest of the vehicle's path, until it leaves the screen
+fn draw_path(canvas: &mut Canvas<Window>, vehicle: &Vehicle, alpha: f32) {
+    let step = 10.0;
+    let (pos_x, pos_y, _) = vehicle.interpolated_pose(alpha);
+    let mut points = vec![Point::new(pos_x.round() as i32, pos_y.round() as i32)];
+    let mut distance = vehicle.distance_travelled;
+    loop {
+        let pose = vehicle.path.pose_at(distance);
+        points.push(Point::new(pose.x.round() as i32, pose.y.round() as i32));
+        let on_screen = (0.0..=WINDOW_WIDTH as f32).contains(&pose.x) && (0.0..=WINDOW_HEIGHT as f32).contains(&pose.y);
+        if !on_screen || points.len() > 500 {
+            break;
+        }
+        distance += step;
+    }
+
+    canvas.set_draw_color(Color::RGBA(120, 220, 255, 160));
+    canvas
+        .draw_lines(points.as_slice())
+        .unwrap_or_else(|e| eprintln!("Error drawing path: {}", e));
+}
+
+fn draw_label(canvas: &mut Canvas<Window>, text: &mut TextCache, vehicle: &Vehicle, alpha: f32) {
+    // The speed changes every frame, so the label would only churn the cache
+    let label = format!("#{} {:.0} px/s", vehicle.id, vehicle.velocity);
+    let (pos_x, pos_y, _) = vehicle.interpolated_pose(alpha);
+    let result = text.uncached(&label, 14, Color::WHITE).and_then(|texture| {
+        let query = texture.query();
+        let x = pos_x.round() as i32 - query.width as i32 / 2;
+        let y = pos_y.round() as i32 - vehicle.length.max(vehicle.width) as i32 - query.height as i32 / 2;
+        canvas.copy(&texture, None, Some(Rect::new(x, y, query.width, query.height)))
+    });
+    result.unwrap_or_else(|e| eprintln!("Error drawing vehicle label: {}", e));
+}
@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+
+use super::car_following::{leader, Gipps};
+use super::conflicts::{conflict_matrix, ConflictMatrix};
+use super::intersection::{still_crossing, stop_at_box};
+use super::constants::*;
+use super::vehicles::{SpeedTier, Vehicle};
+
+/// Right of way for emergency vehicles, on top of whichever intersection policy
+/// is running.
+///
+/// Once an emergency vehicle is within `EMERGENCY_RANGE` of the box, it and the
+/// vehicles ahead of it in its lane ignore the policy and drive on. Every other
+/// vehicle on a route that meets theirs in the conflict matrix, or following
+/// them in their lane, stops short of the box until they have left it. Vehicles
+/// already committed to crossing are let out first: a priority vehicle only
+/// waits at the edge of the box for those, and for other priority vehicles on
+/// routes meeting its own that are closer to the box. The policy no longer
+/// accounts for vehicles let through this way, so vehicles on routes meeting
+/// theirs keep stopping short of the box until they have left it, even once the
+/// emergency vehicle is gone, and vehicles following any vehicle driven off the
+/// policy's plan keep their distance from it.
+pub struct EmergencyPriority {
+    /// Vehicles let through on their way to or across the box that have not
+    /// yet left it
+    let_through: HashSet<u32>,
+    conflicts: &'static ConflictMatrix,
+    car_following: Gipps,
+}
+
+impl EmergencyPriority {
+    pub fn new() -> Self {
+        EmergencyPriority {
+            let_through: HashSet::new(),
+            conflicts: conflict_matrix(),
+            car_following: Gipps::default(),
+        }
+    }
+
+    /// Override the policy's `targets` for `vehicles` to clear the way for
+    /// emergency vehicles. Returns the ids of the vehicles whose targets were
+    /// overridden, as they no longer keep to the policy's plan.
+    pub fn give_way(&mut self, vehicles: &[Vehicle], targets: &mut [f32], dt: f32) -> Vec<u32> {
+        self.let_through.retain(|&id| still_crossing(vehicles, id));
+        let emergencies: Vec<&Vehicle> = vehicles
+            .iter()
+            .filter(|vehicle| vehicle.emergency && !vehicle.has_cleared_intersection())
+            .filter(|vehicle| vehicle.distance_to_intersection().is_none_or(|d| d <= EMERGENCY_RANGE))
+            .collect();
+        if emergencies.is_empty() && self.let_through.is_empty() {
+            return Vec::new();
+        }
+
+        // Vehicles ahead of an emergency vehicle in its lane have to go first
+        // to make way for it
+        let priority: Vec<bool> = vehicles
+            .iter()
+            .map(|vehicle| {
+                emergencies.iter().any(|emergency| {
+                    vehicle.id == emergency.id
+                        || (vehicle.distance_to_intersection().is_some()
+                            && emergency.distance_to_intersection().is_some()
+                            && emergency.gap_to(vehicle).is_some())
+                })
+            })
+            .collect();
+        let committed: Vec<&Vehicle> = vehicles
+            .iter()
+            .zip(&priority)
+            .filter(|&(vehicle, &priority)| !priority && is_committed(vehicle, dt))
+            .map(|(vehicle, _)| vehicle)
+            .collect();
+        let priority_vehicles: Vec<&Vehicle> = vehicles
+            .iter()
+            .zip(&priority)
+            .filter(|&(_, &priority)| priority)
+            .map(|(vehicle, _)| vehicle)
+            .collect();
+        let occupants: Vec<&Vehicle> = vehicles
+            .iter()
+            .filter(|vehicle| self.let_through.contains(&vehicle.id) && vehicle.distance_to_intersection().is_none())
+            .collect();
+
+        let mut overridden = Vec::new();
+        for ((vehicle, &priority), target) in vehicles.iter().zip(&priority).zip(targets.iter_mut()) {
+            let conflicts =
+                |other: &&Vehicle| other.id != vehicle.id && self.conflicts.conflicts(vehicle.route, other.route);
+            if priority {
+                // Priority vehicles on routes that meet go one at a time, the
+                // one closest to the box first
+                let contested = committed.iter().any(conflicts)
+                    || priority_vehicles
+                        .iter()
+                        .filter(|other| conflicts(other))
+                        .any(|other| (progress(other), other.id) < (progress(vehicle), vehicle.id));
+                if !contested {
+                    *target = self.cruise(vehicle, vehicles);
+                    overridden.push(vehicle.id);
+                    self.let_through.insert(vehicle.id);
+                } else if let (false, Some(distance)) = (is_committed(vehicle, dt), vehicle.distance_to_intersection()) {
+                    let stop = stop_at_box(vehicle, distance);
+                    *target = self.cruise(vehicle, vehicles).min(stop);
+                    overridden.push(vehicle.id);
+                }
+                // A committed vehicle keeps to the policy's plan until the way is clear
+            } else if let Some(distance) = vehicle.distance_to_intersection() {
+                let behind = priority_vehicles.iter().any(|other| vehicle.gap_to(other).is_some());
+                let yielding = behind || priority_vehicles.iter().chain(&occupants).any(conflicts);
+                if !is_committed(vehicle, dt) && yielding {
+                    let stop = stop_at_box(vehicle, distance);
+                    let follow = self.car_following.velocity(vehicle, *target, leader(vehicle, vehicles));
+                    *target = target.min(stop).min(follow);
+                    overridden.push(vehicle.id);
+                }
+            }
+        }
+
+        // Vehicles following one pulled off the policy's plan can no longer
+        // count on that plan either, so they keep their distance from it.
+        // Front to back, so the slowdown passes down the lane.
+        let mut order: Vec<usize> = (0..vehicles.len()).collect();
+        order.sort_by(|&a, &b| progress(&vehicles[a]).total_cmp(&progress(&vehicles[b])));
+        for i in order {
+            let vehicle = &vehicles[i];
+            let following = vehicles
+                .iter()
+                .any(|other| overridden.contains(&other.id) && vehicle.gap_to(other).is_some());
+            if vehicle.distance_to_intersection().is_none() || overridden.contains(&vehicle.id) || !following {
+                continue;
+            }
+            let follow = self.car_following.velocity(vehicle, targets[i], leader(vehicle, vehicles));
+            if follow < targets[i] {
+                targets[i] = follow;
+                overridden.push(vehicle.id);
+            }
+        }
+        overridden
+    }
+
+    /// Target of a vehicle with right of way: keep going, only minding the
+    /// vehicle ahead
+    fn cruise(&self, vehicle: &Vehicle, vehicles: &[Vehicle]) -> f32 {
+        let desired = if vehicle.emergency {
+            SpeedTier::Fast.velocity()
+        } else {
+            SpeedTier::Medium.velocity()
+        };
+        self.car_following.velocity(vehicle, desired, leader(vehicle, vehicles))
+    }
+}
+
+impl Default for EmergencyPriority {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `vehicle` is in the box or moving too fast to stop short of it,
+/// even braking as hard as it can from the next step. Held vehicles aim to
+/// stop `STOP_MARGIN` short, which leaves them that much slack here.
+fn is_committed(vehicle: &Vehicle, dt: f32) -> bool {
+    match vehicle.distance_to_intersection() {
+        Some(distance) => vehicle.stopping_velocity(distance) < vehicle.velocity - vehicle.max_deceleration * dt,
+        None => !vehicle.has_cleared_intersection(),
+    }
+}
+
+/// Distance still to go to the box; zero once in it
+fn progress(vehicle: &Vehicle) -> f32 {
+    vehicle.distance_to_intersection().unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::intersection::tests::drive;
+    use crate::internal::policy::POLICIES;
+
+    #[test]
+    fn keeps_vehicles_apart_and_lets_every_one_through_under_every_policy() {
+        for policy in POLICIES.iter().filter(|&&policy| policy != "none") {
+            let simulation = drive(policy, 4, true);
+            let emergencies = simulation.spawns.iter().filter(|spawn| spawn.emergency).count();
+            assert_eq!(emergencies, 3, "{}", policy);
+            assert_eq!(simulation.statistics.emergency_passed as usize, emergencies, "{}", policy);
+        }
+    }
+}
@@ -1,33 +1,37 @@
 use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window};
 
 use super::{
+    config::config,
     constants::*,
-    lanes::{draw_lanes, Direction},
+    lanes::{draw_lanes, get_intersection_boundaries},
+    text::TextCache,
+    vehicles::Direction,
 };
 
-pub fn draw_env(canvas: &mut Canvas<Window>) {
+pub fn draw_env(canvas: &mut Canvas<Window>, text: &mut TextCache) {
     // Set the background color to dark gray
     canvas.set_draw_color(Color::RGB(50, 50, 50));
 
+    let config = config();
+    let (west, _, north, _) = get_intersection_boundaries();
+
     // Draw vertical road
-    let vertical_road_pos = (WINDOW_WIDTH / 2) as i32 - (ROAD_VERTICAL_WIDTH / 2) as i32;
     canvas
         .fill_rect(Rect::new(
-            vertical_road_pos,
+            west,
             0,
-            ROAD_VERTICAL_WIDTH,
+            config.vertical_road_width().round() as u32,
             WINDOW_HEIGHT,
         ))
         .unwrap_or_else(|e| eprintln!("Error drawing vertical road: {}", e));
 
     // Draw horizontal road
-    let horizontal_road_pos = (WINDOW_HEIGHT / 2) as i32 - (ROAD_HORIZONTAL_WIDTH / 2) as i32;
     canvas
         .fill_rect(Rect::new(
             0,
-            horizontal_road_pos,
+            north,
             WINDOW_WIDTH,
-            ROAD_HORIZONTAL_WIDTH,
+            config.horizontal_road_width().round() as u32,
         ))
         .unwrap_or_else(|e| eprintln!("Error drawing horizontal road: {}", e));
 
@@ -35,8 +39,8 @@ pub fn draw_env(canvas: &mut Canvas<Window>) {
     canvas.set_draw_color(Color::RGB(255, 255, 50));
 
     // Draw lanes for each direction
-    draw_lanes(canvas, Direction::West);
-    draw_lanes(canvas, Direction::North);
-    draw_lanes(canvas, Direction::South);
-    draw_lanes(canvas, Direction::East);
+    draw_lanes(canvas, text, Direction::West);
+    draw_lanes(canvas, text, Direction::North);
+    draw_lanes(canvas, text, Direction::South);
+    draw_lanes(canvas, text, Direction::East);
 }
@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::simulation::Simulation;
+use super::vehicles::Vehicle;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+const SAMPLE_FIELDS: [&str; 13] = [
+    "tick",
+    "time",
+    "id",
+    "pos_x",
+    "pos_y",
+    "heading",
+    "velocity",
+    "direction",
+    "lane",
+    "turn_direction",
+    "turning",
+    "in_intersection",
+    "state",
+];
+
+const SUMMARY_FIELDS: [&str; 12] = [
+    "id",
+    "vehicle_type",
+    "spawn_tick",
+    "exit_tick",
+    "direction",
+    "lane",
+    "turn_direction",
+    "exit_direction",
+    "exit_lane",
+    "distance",
+    "min_velocity",
+    "max_velocity",
+];
+
+/// A value in an exported record
+enum Field {
+    Number(String),
+    Text(String),
+    Flag(bool),
+}
+
+/// Writes vehicle trajectories for offline analysis: every vehicle's pose and
+/// state each `every` ticks to one file, and a summary of each vehicle as it
+/// leaves the screen to a second file next to it (`trajectories.csv` and
+/// `trajectories.summary.csv`). Lanes are numbered from 1, as in the config.
+pub struct TrajectoryExporter {
+    format: ExportFormat,
+    every: u64,
+    samples: BufWriter<File>,
+    summaries: BufWriter<File>,
+}
+
+impl TrajectoryExporter {
+    /// Create the two files, in the format given by the extension of `path`:
+    /// `.csv`, or `.ndjson`/`.jsonl` for JSON Lines
+    pub fn create(path: &str, every: u64) -> Result<Self, String> {
+        let (stem, extension) = path
+            .rsplit_once('.')
+            .ok_or(format!("Export file {} needs a .csv, .ndjson or .jsonl extension", path))?;
+        let format = match extension {
+            "csv" => ExportFormat::Csv,
+            "ndjson" | "jsonl" => ExportFormat::Ndjson,
+            _ => return Err(format!("Unknown export format .{}, use .csv, .ndjson or .jsonl", extension)),
+        };
+        let summary_path = format!("{}.summary.{}", stem, extension);
+        let open = |path: &str| {
+            File::create(path)
+                .map(BufWriter::new)
+                .map_err(|e| format!("Cannot create {}: {}", path, e))
+        };
+
+        let mut exporter = TrajectoryExporter {
+            format,
+            every: every.max(1),
+            samples: open(path)?,
+            summaries: open(&summary_path)?,
+        };
+        if format == ExportFormat::Csv {
+            writeln!(exporter.samples, "{}", SAMPLE_FIELDS.join(",")).map_err(write_error)?;
+            writeln!(exporter.summaries, "{}", SUMMARY_FIELDS.join(",")).map_err(write_error)?;
+        }
+        Ok(exporter)
+    }
+
+    /// Export the simulation as it is after its last step
+    pub fn record(&mut self, simulation: &Simulation) -> Result<(), String> {
+        let tick = simulation.tick;
+        if tick % self.every == 0 {
+            for vehicle in &simulation.vehicles {
+                let record = sample(vehicle, tick, simulation.time);
+                write_record(&mut self.samples, self.format, &SAMPLE_FIELDS, record)?;
+            }
+        }
+
+        for vehicle in &simulation.removed {
+            let record = summary(vehicle, tick);
+            write_record(&mut self.summaries, self.format, &SUMMARY_FIELDS, record)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        self.samples.flush().map_err(write_error)?;
+        self.summaries.flush().map_err(write_error)
+    }
+}
+
+fn sample(vehicle: &Vehicle, tick: u64, time: f64) -> Vec<Field> {
+    vec![
+        Field::Number(tick.to_string()),
+        Field::Number(format!("{:.4}", time)),
+        Field::Number(vehicle.id.to_string()),
+        Field::Number(format!("{:.2}", vehicle.pos_x)),
+        Field::Number(format!("{:.2}", vehicle.pos_y)),
+        Field::Number(format!("{:.4}", vehicle.heading)),
+        Field::Number(format!("{:.2}", vehicle.velocity)),
+        Field::Text(format!("{:?}", vehicle.direction)),
+        Field::Number((vehicle.lane + 1).to_string()),
+        Field::Text(format!("{:?}", vehicle.turn_direction)),
+        Field::Flag(vehicle.turning),
+        Field::Flag(vehicle.in_intersection()),
+        Field::Text(format!("{:?}", vehicle.state)),
+    ]
+}
+
+fn summary(vehicle: &Vehicle, exit_tick: u64) -> Vec<Field> {
+    let route = vehicle.route;
+    vec![
+        Field::Number(vehicle.id.to_string()),
+        Field::Text(format!("{:?}", vehicle.vehicle_type)),
+        Field::Number(vehicle.spawn_tick.to_string()),
+        Field::Number(exit_tick.to_string()),
+        Field::Text(format!("{:?}", route.direction)),
+        Field::Number((route.lane + 1).to_string()),
+        Field::Text(format!("{:?}", route.turn_direction)),
+        Field::Text(format!("{:?}", route.exit_direction())),
+        Field::Number((route.target_lane() + 1).to_string()),
+        Field::Number(format!("{:.2}", vehicle.distance_travelled)),
+        Field::Number(format!("{:.2}", vehicle.min_velocity)),
+        Field::Number(format!("{:.2}", vehicle.max_velocity)),
+    ]
+}
+
+/// Write one record as a CSV row or a JSON object; field names and values
+/// never contain characters that need quoting
+fn write_record(out: &mut impl Write, format: ExportFormat, names: &[&str], values: Vec<Field>) -> Result<(), String> {
+    let line = match format {
+        ExportFormat::Csv => values
+            .into_iter()
+            .map(|value| match value {
+                Field::Number(text) | Field::Text(text) => text,
+                Field::Flag(flag) => flag.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        ExportFormat::Ndjson => {
+            let members: Vec<String> = names
+                .iter()
+                .zip(values)
+                .map(|(name, value)| match value {
+                    Field::Number(text) => format!("\"{}\":{}", name, text),
+                    Field::Text(text) => format!("\"{}\":\"{}\"", name, text),
+                    Field::Flag(flag) => format!("\"{}\":{}", name, flag),
+                })
+                .collect();
+            format!("{{{}}}", members.join(","))
+        }
+    };
+    writeln!(out, "{}", line).map_err(write_error)
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("Error writing trajectories: {}", e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::policy::policy_named;
+    use crate::internal::vehicles::{Direction, TurnDirection};
+
+    fn vehicle() -> Vehicle {
+        let mut vehicle = Vehicle::new(Direction::East, 3, TurnDirection::Left);
+        vehicle.id = 7;
+        vehicle
+    }
+
+    fn written(format: ExportFormat, names: &[&str], values: Vec<Field>) -> String {
+        let mut out = Vec::new();
+        write_record(&mut out, format, names, values).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let row = written(ExportFormat::Csv, &SAMPLE_FIELDS, sample(&vehicle(), 12, 0.2));
+        let fields: Vec<&str> = row.trim_end().split(',').collect();
+        assert_eq!(fields.len(), SAMPLE_FIELDS.len());
+        assert_eq!(&fields[..3], ["12", "0.2000", "7"]);
+        assert_eq!(&fields[7..11], ["East", "4", "Left", "false"]);
+
+        let row = written(ExportFormat::Csv, &SUMMARY_FIELDS, summary(&vehicle(), 40));
+        assert_eq!(row.trim_end().split(',').count(), SUMMARY_FIELDS.len());
+    }
+
+    #[test]
+    fn ndjson_lines_are_objects_with_named_fields() {
+        let line = written(ExportFormat::Ndjson, &SAMPLE_FIELDS, sample(&vehicle(), 12, 0.2));
+        assert!(line.starts_with("{\"tick\":12,\"time\":0.2000,\"id\":7,"), "{}", line);
+        assert!(line.contains("\"direction\":\"East\",\"lane\":4,\"turn_direction\":\"Left\",\"turning\":false"));
+        assert!(line.ends_with("}\n"));
+        assert_eq!(line.lines().count(), 1);
+    }
+
+    #[test]
+    fn exports_every_nth_tick_and_each_vehicle_that_leaves() {
+        let dir = std::env::temp_dir().join(format!("01-road-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("run.ndjson").to_string_lossy().into_owned();
+
+        let mut exporter = TrajectoryExporter::create(&path, 30).unwrap();
+        let mut simulation = Simulation::new(policy_named("conflict").unwrap(), 3);
+        let mut left = 0;
+        while left < 3 {
+            simulation.step();
+            exporter.record(&simulation).unwrap();
+            left += simulation.removed.len();
+        }
+        exporter.finish().unwrap();
+
+        let samples = std::fs::read_to_string(&path).unwrap();
+        let summaries = std::fs::read_to_string(dir.join("run.summary.ndjson")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(!samples.is_empty());
+        for line in samples.lines() {
+            let tick: u64 = line["{\"tick\":".len()..].split(',').next().unwrap().parse().unwrap();
+            assert_eq!(tick % 30, 0, "{}", line);
+        }
+        assert_eq!(summaries.lines().count(), left);
+    }
+
+    #[test]
+    fn rejects_unknown_extensions() {
+        assert!(TrajectoryExporter::create("run.txt", 1).is_err());
+        assert!(TrajectoryExporter::create("run", 1).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+/// Corners of a rectangle centred on `(x, y)` and rotated to `heading` (radians),
+/// with `half_length` along the heading and `half_width` across it
+pub fn oriented_rect(x: f32, y: f32, heading: f32, half_length: f32, half_width: f32) -> [(f32, f32); 4] {
+    let (sin, cos) = (libm::sinf(heading), libm::cosf(heading));
+    let (along_x, along_y) = (cos * half_length, sin * half_length);
+    let (across_x, across_y) = (-sin * half_width, cos * half_width);
+    [
+        (x + along_x + across_x, y + along_y + across_y),
+        (x + along_x - across_x, y + along_y - across_y),
+        (x - along_x - across_x, y - along_y - across_y),
+        (x - along_x + across_x, y - along_y + across_y),
+    ]
+}
+
+/// Axis-aligned bounds of a polygon as (left, top, right, bottom)
+pub fn polygon_bounds(polygon: &[(f32, f32)]) -> (f32, f32, f32, f32) {
+    polygon.iter().fold(
+        (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+        |(left, top, right, bottom), &(x, y)| (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+    )
+}
+
+/// Whether two convex polygons overlap, by the separating axis test
+pub fn polygons_overlap(a: &[(f32, f32)], b: &[(f32, f32)]) -> bool {
+    edge_normals(a).chain(edge_normals(b)).all(|axis| {
+        let (a_min, a_max) = project(a, axis);
+        let (b_min, b_max) = project(b, axis);
+        a_min < b_max && b_min < a_max
+    })
+}
+
+/// Whether a rectangle from `oriented_rect` overlaps the axis-aligned square
+/// with top-left `corner` and side `size`. Only the rectangle's own axes need
+/// testing when the square is already known to be inside the rectangle's bounds.
+pub fn rect_overlaps_square(rect: &[(f32, f32); 4], corner: (f32, f32), size: f32) -> bool {
+    let center = ((rect[0].0 + rect[2].0) / 2.0, (rect[0].1 + rect[2].1) / 2.0);
+    let half = size / 2.0;
+    let offset = (corner.0 + half - center.0, corner.1 + half - center.1);
+    // Half the rectangle's length and half its width, as vectors
+    let along = ((rect[0].0 - rect[3].0) / 2.0, (rect[0].1 - rect[3].1) / 2.0);
+    let across = ((rect[0].0 - rect[1].0) / 2.0, (rect[0].1 - rect[1].1) / 2.0);
+    [along, across].iter().all(|&(x, y)| {
+        let distance = (offset.0 * x + offset.1 * y).abs();
+        distance < x * x + y * y + half * (x.abs() + y.abs())
+    })
+}
+
+/// Shortest distance between two convex polygons, zero if they overlap
+pub fn polygon_distance(a: &[(f32, f32)], b: &[(f32, f32)]) -> f32 {
+    if polygons_overlap(a, b) {
+        return 0.0;
+    }
+
+    let closest = |points: &[(f32, f32)], polygon: &[(f32, f32)]| {
+        points
+            .iter()
+            .flat_map(|&point| edges(polygon).map(move |(start, end)| point_segment_distance(point, start, end)))
+            .fold(f32::MAX, f32::min)
+    };
+    closest(a, b).min(closest(b, a))
+}
+
+fn edges(polygon: &[(f32, f32)]) -> impl Iterator<Item = ((f32, f32), (f32, f32))> + '_ {
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(&start, &end)| (start, end))
+}
+
+fn edge_normals(polygon: &[(f32, f32)]) -> impl Iterator<Item = (f32, f32)> + '_ {
+    edges(polygon).map(|(start, end)| (start.1 - end.1, end.0 - start.0))
+}
+
+fn project(polygon: &[(f32, f32)], axis: (f32, f32)) -> (f32, f32) {
+    polygon
+        .iter()
+        .map(|&(x, y)| x * axis.0 + y * axis.1)
+        .fold((f32::MAX, f32::MIN), |(min, max), value| (min.min(value), max.max(value)))
+}
+
+fn point_segment_distance(point: (f32, f32), start: (f32, f32), end: (f32, f32)) -> f32 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared > 0.0 {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (closest_x, closest_y) = (start.0 + t * dx, start.1 + t * dy);
+    let (off_x, off_y) = (point.0 - closest_x, point.1 - closest_y);
+    (off_x * off_x + off_y * off_y).sqrt()
+}
@@ -0,0 +1,83 @@
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{BlendMode, Canvas},
+    video::Window,
+};
+
+use super::constants::*;
+use super::simulation::Simulation;
+use super::text::TextCache;
+
+/// Live traffic figures in the top left corner of the main window
+pub struct Hud {
+    /// Frames and real seconds counted towards the next FPS reading
+    frames: u32,
+    elapsed: f32,
+    fps: f32,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Hud {
+            frames: 0,
+            elapsed: 0.0,
+            fps: 0.0,
+        }
+    }
+
+    /// Count a frame that took `frame_time` real seconds
+    pub fn frame(&mut self, frame_time: f32) {
+        self.frames += 1;
+        self.elapsed += frame_time;
+        if self.elapsed >= HUD_FPS_INTERVAL {
+            self.fps = self.frames as f32 / self.elapsed;
+            self.frames = 0;
+            self.elapsed = 0.0;
+        }
+    }
+
+    /// Draw the figures for `simulation` running at `speed` times real time,
+    /// or paused if `speed` is `None`
+    pub fn draw(&self, canvas: &mut Canvas<Window>, text: &mut TextCache, simulation: &Simulation, speed: Option<f32>) {
+        let statistics = &simulation.statistics;
+        let average_wait = match statistics.average_wait() {
+            Some(wait) => format!("{:.1} s", wait),
+            None => "-".to_string(),
+        };
+        let speed = match speed {
+            Some(speed) => format!("{}x", speed),
+            None => "paused".to_string(),
+        };
+        let lines = [
+            format!("Vehicles: {}", simulation.vehicles.len()),
+            format!("Throughput: {}", statistics.vehicles_passed),
+            format!("Per minute: {:.1}", statistics.throughput_per_minute(simulation.time)),
+            format!("Average wait: {}", average_wait),
+            format!("Close calls: {}", statistics.close_calls),
+            format!("Speed: {}", speed),
+            format!("FPS: {:.0}", self.fps),
+            format!("Random spawning: {}", if simulation.random_spawning { "on" } else { "off" }),
+        ];
+
+        let (x, y) = (HUD_MARGIN, HUD_MARGIN);
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        canvas
+            .fill_rect(Rect::new(x, y, HUD_WIDTH, lines.len() as u32 * HUD_LINE_HEIGHT + 8))
+            .unwrap_or_else(|e| eprintln!("Error drawing HUD: {}", e));
+        canvas.set_blend_mode(BlendMode::None);
+
+        for (i, line) in lines.iter().enumerate() {
+            let position = (x + 8, y + 4 + (i as u32 * HUD_LINE_HEIGHT) as i32);
+            text.draw(canvas, line, 15, Color::WHITE, position, None)
+                .unwrap_or_else(|e| eprintln!("Error drawing HUD: {}", e));
+        }
+    }
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
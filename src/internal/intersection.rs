@@ -0,0 +1,323 @@
+use std::collections::{HashMap, HashSet};
+
+use super::car_following::{leader, Gipps};
+use super::conflicts::{conflict_matrix, ConflictMatrix};
+use super::constants::*;
+use super::geometry::{polygon_bounds, rect_overlaps_square};
+use super::policy::IntersectionPolicy;
+use super::signals::{PhaseOrder, Signal};
+use super::vehicles::{Route, SpeedTier, Vehicle};
+
+/// Which of the vehicles that have reached the decision point may be cleared,
+/// on top of never clearing a vehicle whose route conflicts with one already
+/// cleared
+pub enum Admission {
+    /// Any vehicle whose route is free
+    FirstClear,
+    /// Only the vehicle that reached the decision point first of those still
+    /// waiting, so nobody overtakes an earlier arrival
+    ArrivalOrder,
+    /// Only vehicles on the approach the signal shows green to
+    Signal(Signal),
+}
+
+/// Decides every tick which vehicles may enter the intersection.
+///
+/// When the front vehicle of a lane gets close enough that it must either start
+/// braking or commit, it is cleared to enter only if no vehicle already cleared
+/// is on a route that meets its own in the conflict matrix, and the admission
+/// rule lets it go. Routes that never meet, such as the four right turns, share
+/// the box freely. Otherwise it brakes to a stop at the edge of the box and
+/// tries again, so vehicles on crossing paths are never in the box together.
+pub struct IntersectionManager {
+    admission: Admission,
+    /// Order in which the vehicles not yet cleared reached the decision point
+    arrivals: HashMap<u32, u64>,
+    next_arrival: u64,
+    footprints: RouteFootprints,
+    conflicts: &'static ConflictMatrix,
+    /// Vehicles cleared to enter that have not yet left the box
+    cleared: HashMap<u32, Route>,
+    car_following: Gipps,
+}
+
+impl IntersectionManager {
+    pub fn new() -> Self {
+        Self::with_admission(Admission::FirstClear)
+    }
+
+    pub fn with_admission(admission: Admission) -> Self {
+        IntersectionManager {
+            admission,
+            arrivals: HashMap::new(),
+            next_arrival: 0,
+            footprints: RouteFootprints::new(),
+            conflicts: conflict_matrix(),
+            cleared: HashMap::new(),
+            car_following: Gipps::default(),
+        }
+    }
+
+    pub fn conflicts(&self, a: Route, b: Route) -> bool {
+        self.conflicts.conflicts(a, b)
+    }
+
+    /// Whether the admission rule lets `vehicle` go now
+    fn admits(&self, vehicle: &Vehicle) -> bool {
+        match &self.admission {
+            Admission::FirstClear => true,
+            Admission::ArrivalOrder => {
+                let arrival = self.arrivals.get(&vehicle.id);
+                arrival.is_some() && self.arrivals.values().min() == arrival
+            }
+            Admission::Signal(signal) => signal.is_green(vehicle.route.direction),
+        }
+    }
+}
+
+impl IntersectionPolicy for IntersectionManager {
+    fn name(&self) -> &'static str {
+        match &self.admission {
+            Admission::FirstClear => "conflict",
+            Admission::ArrivalOrder => "fcfs",
+            Admission::Signal(signal) => match signal.order() {
+                PhaseOrder::Rotation => "traffic-light",
+                PhaseOrder::LongestQueue => "longest-queue",
+            },
+        }
+    }
+
+    fn held_tiles(&self) -> Vec<(u32, (i32, i32))> {
+        self.footprints.held(&self.cleared)
+    }
+
+    fn update(&mut self, vehicles: &[Vehicle], dt: f32) -> Vec<f32> {
+        self.cleared.retain(|&id, _| still_crossing(vehicles, id));
+        self.arrivals
+            .retain(|&id, _| vehicles.iter().any(|vehicle| vehicle.id == id));
+        let box_clear = self.cleared.is_empty();
+        if let Admission::Signal(signal) = &mut self.admission {
+            signal.update(vehicles, box_clear, dt);
+        }
+
+        let mut targets = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            // Queue behind the vehicle ahead in the same lane
+            let mut target = self.car_following.velocity(
+                vehicle,
+                SpeedTier::Medium.velocity(),
+                leader(vehicle, vehicles),
+            );
+
+            if let Some(distance) = decision_distance(vehicle, dt) {
+                if !self.cleared.contains_key(&vehicle.id) {
+                    if !self.arrivals.contains_key(&vehicle.id) {
+                        self.arrivals.insert(vehicle.id, self.next_arrival);
+                        self.next_arrival += 1;
+                    }
+
+                    // Only the front vehicle of a lane is considered
+                    let clear = is_front_of_lane(vehicle, vehicles, |id| self.cleared.contains_key(&id))
+                        && self.admits(vehicle)
+                        && self
+                            .cleared
+                            .values()
+                            .all(|&other| !self.conflicts(vehicle.route, other));
+
+                    if clear {
+                        self.cleared.insert(vehicle.id, vehicle.route);
+                        self.arrivals.remove(&vehicle.id);
+                    } else {
+                        target = target.min(stop_at_box(vehicle, distance));
+                    }
+                }
+            }
+
+            targets.push(target);
+        }
+
+        targets
+    }
+}
+
+impl Default for IntersectionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Tiles each route covers inside the box, for showing what the vehicles a
+/// policy has let in hold
+pub struct RouteFootprints {
+    tiles: HashMap<Route, HashSet<(i32, i32)>>,
+}
+
+impl RouteFootprints {
+    pub fn new() -> Self {
+        RouteFootprints {
+            tiles: Route::all()
+                .into_iter()
+                .map(|route| (route, trace_route(route)))
+                .collect(),
+        }
+    }
+
+    /// Tiles of the conflict zone each vehicle let in holds, as (vehicle id,
+    /// tile): the whole footprint of its route
+    pub fn held(&self, admitted: &HashMap<u32, Route>) -> Vec<(u32, (i32, i32))> {
+        admitted
+            .iter()
+            .flat_map(|(&id, route)| self.tiles[route].iter().map(move |&tile| (id, tile)))
+            .collect()
+    }
+}
+
+impl Default for RouteFootprints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Distance to the box of a vehicle close enough that it must either start
+/// braking or commit to entering; `None` further out or once in the box
+pub fn decision_distance(vehicle: &Vehicle, dt: f32) -> Option<f32> {
+    vehicle
+        .distance_to_intersection()
+        .filter(|&distance| distance <= vehicle.braking_distance() + vehicle.velocity * dt + DECISION_MARGIN)
+}
+
+/// Target that brakes a vehicle `distance` from the box to a stop at its edge
+pub fn stop_at_box(vehicle: &Vehicle, distance: f32) -> f32 {
+    vehicle.stopping_velocity(distance - STOP_MARGIN)
+}
+
+/// Whether the vehicle `id` is still around and has not yet left the box, for
+/// dropping vehicles a policy let in once they are through
+pub fn still_crossing(vehicles: &[Vehicle], id: u32) -> bool {
+    vehicles
+        .iter()
+        .any(|vehicle| vehicle.id == id && !vehicle.has_cleared_intersection())
+}
+
+/// Whether `vehicle` is at the front of its lane: no vehicle ahead of it in the
+/// lane is still to enter the box, leaving out those `admitted` already
+pub fn is_front_of_lane(vehicle: &Vehicle, vehicles: &[Vehicle], admitted: impl Fn(u32) -> bool) -> bool {
+    !vehicles.iter().any(|other| {
+        vehicle.gap_to(other).is_some() && !other.has_cleared_intersection() && !admitted(other.id)
+    })
+}
+
+/// Tiles covered by a vehicle driving `route` while it is inside the intersection
+pub fn trace_route(route: Route) -> HashSet<(i32, i32)> {
+    let mut ghost = Vehicle::new(route.direction, route.lane, route.turn_direction);
+    let mut tiles = HashSet::new();
+
+    while ghost.is_on_screen() {
+        if ghost.in_intersection() {
+            tiles.extend(covered_tiles(ghost.footprint(0.0)));
+        }
+        ghost.move_vehicle(SpeedTier::Medium.velocity(), SIMULATION_DT);
+    }
+
+    tiles
+}
+
+/// Grid tiles of `TILE_SIZE` that overlap a vehicle's (rotated) footprint
+pub fn covered_tiles(footprint: [(f32, f32); 4]) -> impl Iterator<Item = (i32, i32)> {
+    let (left, top, right, bottom) = polygon_bounds(&footprint);
+    let size = TILE_SIZE as f32;
+    let tile = |coord: f32| (coord / size).floor() as i32;
+    let xs = tile(left)..=tile(right);
+    let ys = tile(top)..=tile(bottom);
+    xs.flat_map(move |tile_x| ys.clone().map(move |tile_y| (tile_x, tile_y)))
+        .filter(move |&(tile_x, tile_y)| {
+            rect_overlaps_square(&footprint, (tile_x as f32 * size, tile_y as f32 * size), size)
+        })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::internal::geometry::polygons_overlap;
+    use crate::internal::policy::policy_named;
+    use crate::internal::simulation::{Input, Simulation};
+    use crate::internal::vehicles::Direction;
+
+    /// Run the policy called `policy` on seeded traffic from every approach
+    /// for half a minute, with an emergency vehicle every ten seconds if
+    /// `emergencies`, then stop spawning until every vehicle has left. Fails
+    /// if two vehicles are ever in the box on top of each other, or if any
+    /// vehicle has not crossed after three more minutes.
+    pub(crate) fn drive(policy: &str, seed: u64, emergencies: bool) -> Simulation {
+        let approaches = [Direction::North, Direction::East, Direction::South, Direction::West];
+        let mut simulation = Simulation::new(policy_named(policy).unwrap(), seed);
+        let mut emergencies_due = 0;
+        while simulation.time < 30.0 {
+            if emergencies && simulation.tick % 600 == 300 {
+                emergencies_due += 1;
+            }
+            // Keep trying until there is room for the emergency vehicle
+            let emergencies_spawned = simulation.spawns.iter().filter(|spawn| spawn.emergency).count();
+            if emergencies_spawned < emergencies_due {
+                simulation.apply(Input::SpawnEmergency);
+            } else if simulation.tick % 30 == 0 {
+                simulation.apply(Input::Spawn(approaches[(simulation.tick / 30 % 4) as usize]));
+            }
+            step_apart(&mut simulation);
+        }
+        simulation.apply(Input::ToggleRandomSpawning);
+        while !simulation.vehicles.is_empty() {
+            assert!(simulation.time < 210.0, "{} vehicles never got through", simulation.vehicles.len());
+            step_apart(&mut simulation);
+        }
+        assert_eq!(simulation.statistics.vehicles_passed as usize, simulation.spawns.len());
+        simulation
+    }
+
+    fn step_apart(simulation: &mut Simulation) {
+        simulation.step();
+        let in_box: Vec<_> = simulation
+            .vehicles
+            .iter()
+            .filter(|vehicle| vehicle.in_intersection())
+            .map(|vehicle| (vehicle.id, vehicle.footprint(0.0)))
+            .collect();
+        for (i, (id, footprint)) in in_box.iter().enumerate() {
+            for (other, other_footprint) in &in_box[i + 1..] {
+                assert!(
+                    !polygons_overlap(footprint, other_footprint),
+                    "vehicles {} and {} overlap in the box at {:.2} s",
+                    id,
+                    other,
+                    simulation.time
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn conflict_keeps_vehicles_apart_and_lets_every_one_through() {
+        for seed in 1..=3 {
+            drive("conflict", seed, false);
+        }
+    }
+    #[test]
+    fn fcfs_keeps_vehicles_apart_and_lets_every_one_through() {
+        for seed in 1..=2 {
+            drive("fcfs", seed, false);
+        }
+    }
+
+    #[test]
+    fn traffic_light_keeps_vehicles_apart_and_lets_every_one_through() {
+        for seed in 1..=2 {
+            drive("traffic-light", seed, false);
+        }
+    }
+
+    #[test]
+    fn longest_queue_keeps_vehicles_apart_and_lets_every_one_through() {
+        for seed in 1..=2 {
+            drive("longest-queue", seed, false);
+        }
+    }
+}
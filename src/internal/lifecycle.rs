@@ -0,0 +1,154 @@
+use super::config::config;
+use super::constants::*;
+use super::vehicles::Vehicle;
+
+/// Where a vehicle is in its trip through the intersection. A vehicle moves
+/// between `Approaching` and `Waiting` as its queue stops and starts, and
+/// otherwise only ever moves forward through the list.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum LifecycleState {
+    /// On its way to the box
+    Approaching,
+    /// Stopped before the box
+    Waiting,
+    /// Front inside the box, back still outside
+    Entering,
+    /// Wholly inside the box
+    InIntersection,
+    /// Front through the far side of the box
+    Exiting,
+    /// Off the screen and removed from the simulation
+    Despawned,
+}
+
+impl LifecycleState {
+    /// State of a vehicle on screen, judging only by where it is and how fast
+    /// it is going
+    pub fn observe(vehicle: &Vehicle) -> LifecycleState {
+        if vehicle.distance_to_intersection().is_some() {
+            return if vehicle.velocity < WAITING_VELOCITY {
+                LifecycleState::Waiting
+            } else {
+                LifecycleState::Approaching
+            };
+        }
+
+        let (cos, sin) = (libm::cosf(vehicle.heading), libm::sinf(vehicle.heading));
+        let half_length = vehicle.length / 2.0;
+        let front = (vehicle.pos_x + cos * half_length, vehicle.pos_y + sin * half_length);
+        let back = (vehicle.pos_x - cos * half_length, vehicle.pos_y - sin * half_length);
+        match (in_box(front), in_box(back)) {
+            (true, false) => LifecycleState::Entering,
+            (true, true) => LifecycleState::InIntersection,
+            (false, _) => LifecycleState::Exiting,
+        }
+    }
+
+    /// Position along the trip; the queueing states share the first place
+    fn order(self) -> u8 {
+        match self {
+            LifecycleState::Approaching | LifecycleState::Waiting => 0,
+            LifecycleState::Entering => 1,
+            LifecycleState::InIntersection => 2,
+            LifecycleState::Exiting => 3,
+            LifecycleState::Despawned => 4,
+        }
+    }
+
+    /// State after `observed`: never back towards the start of the trip
+    pub fn next(self, observed: LifecycleState) -> LifecycleState {
+        if observed.order() < self.order() {
+            self
+        } else {
+            observed
+        }
+    }
+}
+
+fn in_box((x, y): (f32, f32)) -> bool {
+    let (west, east, north, south) = config().intersection();
+    (west..=east).contains(&x) && (north..=south).contains(&y)
+}
+
+/// A vehicle changing state; `from` is `None` when it has just been spawned
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LifecycleEvent {
+    pub tick: u64,
+    /// Simulated seconds since the start
+    pub time: f64,
+    pub vehicle: u32,
+    pub from: Option<LifecycleState>,
+    pub to: LifecycleState,
+}
+
+/// Anything that wants to hear about lifecycle transitions. Listeners see the
+/// vehicle as it is at the transition, including a despawned vehicle's final
+/// state.
+pub trait LifecycleListener {
+    fn on_lifecycle(&mut self, event: &LifecycleEvent, vehicle: &Vehicle);
+}
+
+impl<F: FnMut(&LifecycleEvent, &Vehicle)> LifecycleListener for F {
+    fn on_lifecycle(&mut self, event: &LifecycleEvent, vehicle: &Vehicle) {
+        self(event, vehicle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::internal::policy::policy_named;
+    use crate::internal::simulation::Simulation;
+
+    const STATES: [LifecycleState; 6] = [
+        LifecycleState::Approaching,
+        LifecycleState::Waiting,
+        LifecycleState::Entering,
+        LifecycleState::InIntersection,
+        LifecycleState::Exiting,
+        LifecycleState::Despawned,
+    ];
+
+    #[test]
+    fn next_never_moves_backwards() {
+        for state in STATES {
+            for observed in STATES {
+                let next = state.next(observed);
+                assert!(next.order() >= state.order(), "{:?} then {:?}", state, observed);
+                assert!(next == state || next == observed);
+            }
+        }
+        assert_eq!(LifecycleState::Waiting.next(LifecycleState::Approaching), LifecycleState::Approaching);
+    }
+
+    #[test]
+    fn published_transitions_run_from_spawn_to_despawn() {
+        let events: Rc<RefCell<Vec<LifecycleEvent>>> = Rc::default();
+        let mut simulation = Simulation::new(policy_named("conflict").unwrap(), 5);
+        let log = Rc::clone(&events);
+        simulation.subscribe(move |event: &LifecycleEvent, _: &Vehicle| log.borrow_mut().push(*event));
+        while simulation.time < 90.0 {
+            simulation.step();
+        }
+
+        let mut current: HashMap<u32, LifecycleState> = HashMap::new();
+        for event in events.borrow().iter() {
+            match event.from {
+                None => assert!(current.insert(event.vehicle, event.to).is_none()),
+                Some(from) => {
+                    assert_eq!(current.get(&event.vehicle), Some(&from));
+                    assert_ne!(from, event.to);
+                    assert!(event.to.order() >= from.order(), "{:?}", event);
+                    current.insert(event.vehicle, event.to);
+                }
+            }
+        }
+        let despawned = current.values().filter(|&&state| state == LifecycleState::Despawned).count();
+        assert!(despawned > 10, "{}", despawned);
+        assert_eq!(current.len() - despawned, simulation.vehicles.len());
+    }
+}
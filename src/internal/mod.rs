@@ -1,5 +1,27 @@
 pub mod sdl;
 pub mod environment;
 pub mod constants;
+pub mod config;
+pub mod routes;
 pub mod lanes;
-pub mod vehicles;
\ No newline at end of file
+pub mod geometry;
+pub mod paths;
+pub mod vehicles;
+pub mod lifecycle;
+pub mod car_following;
+pub mod conflicts;
+pub mod intersection;
+pub mod reservation;
+pub mod signals;
+pub mod platoons;
+pub mod emergency;
+pub mod policy;
+pub mod statistics;
+pub mod close_calls;
+pub mod simulation;
+pub mod recording;
+pub mod export;
+pub mod text;
+pub mod render;
+pub mod debug;
+pub mod hud;
\ No newline at end of file
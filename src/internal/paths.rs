@@ -0,0 +1,193 @@
+use std::f32::consts::FRAC_PI_2;
+
+use super::config::config;
+use super::constants::*;
+use super::vehicles::{horizontal_lane_center, vertical_lane_center, Direction, Route, TurnDirection};
+
+/// Position and heading (radians, screen coordinates) at a point on a path
+#[derive(Clone, Copy, Debug)]
+pub struct Pose {
+    pub x: f32,
+    pub y: f32,
+    pub heading: f32,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Line {
+        start: (f32, f32),
+        end: (f32, f32),
+    },
+    /// Quarter circle around `center`, starting at `start_angle` and sweeping
+    /// `sweep` radians (positive is clockwise on screen)
+    Arc {
+        center: (f32, f32),
+        radius: f32,
+        start_angle: f32,
+        sweep: f32,
+    },
+}
+
+// Trigonometry here and in the rest of the simulation goes through libm
+// rather than the platform's maths library, so the same seed gives the same
+// positions on every machine
+impl Segment {
+    fn length(&self) -> f32 {
+        match *self {
+            Segment::Line { start, end } => libm::hypotf(end.0 - start.0, end.1 - start.1),
+            Segment::Arc { radius, sweep, .. } => radius * sweep.abs(),
+        }
+    }
+
+    fn pose_at(&self, distance: f32) -> Pose {
+        match *self {
+            Segment::Line { start, end } => {
+                let t = distance / self.length();
+                Pose {
+                    x: start.0 + (end.0 - start.0) * t,
+                    y: start.1 + (end.1 - start.1) * t,
+                    heading: libm::atan2f(end.1 - start.1, end.0 - start.0),
+                }
+            }
+            Segment::Arc {
+                center,
+                radius,
+                start_angle,
+                sweep,
+            } => {
+                let angle = start_angle + sweep.signum() * distance / radius;
+                Pose {
+                    x: center.0 + radius * libm::cosf(angle),
+                    y: center.1 + radius * libm::sinf(angle),
+                    heading: angle + sweep.signum() * FRAC_PI_2,
+                }
+            }
+        }
+    }
+}
+
+/// Geometric path of a route: straight through, or straight in, a circular arc
+/// from the inbound lane onto the target lane, and straight out
+#[derive(Clone, Debug)]
+pub struct Path {
+    segments: Vec<Segment>,
+    /// Lane the path leaves the intersection on
+    pub exit_lane: i32,
+}
+
+impl Path {
+    pub fn for_route(route: Route) -> Path {
+        let (in_x, in_y) = unit(route.direction);
+        let start = start_position(route.direction, route.lane);
+        let exit_lane = route.target_lane();
+        // Far enough to take any vehicle off the screen
+        let run_out = WINDOW_WIDTH.max(WINDOW_HEIGHT) as f32;
+
+        if route.turn_direction == TurnDirection::Straight {
+            let end = (start.0 + in_x * 2.0 * run_out, start.1 + in_y * 2.0 * run_out);
+            return Path {
+                segments: vec![Segment::Line { start, end }],
+                exit_lane,
+            };
+        }
+
+        // The inbound lane crosses the centre of the target lane here
+        let corner = match route.direction {
+            Direction::North | Direction::South => {
+                (vertical_lane_center(route.lane), horizontal_lane_center(exit_lane))
+            }
+            Direction::East | Direction::West => {
+                (vertical_lane_center(exit_lane), horizontal_lane_center(route.lane))
+            }
+        };
+
+        // Use the widest arc that stays inside the intersection
+        let exit_direction = route.exit_direction();
+        let (out_x, out_y) = unit(exit_direction);
+        let radius = room_to_edge(corner, route.direction, false)
+            .min(room_to_edge(corner, exit_direction, true));
+        let arc_start = (corner.0 - in_x * radius, corner.1 - in_y * radius);
+        let arc_end = (corner.0 + out_x * radius, corner.1 + out_y * radius);
+        let center = (arc_start.0 + out_x * radius, arc_start.1 + out_y * radius);
+        let clockwise = in_x * out_y - in_y * out_x > 0.0;
+
+        Path {
+            segments: vec![
+                Segment::Line {
+                    start,
+                    end: arc_start,
+                },
+                Segment::Arc {
+                    center,
+                    radius,
+                    start_angle: libm::atan2f(arc_start.1 - center.1, arc_start.0 - center.0),
+                    sweep: if clockwise { FRAC_PI_2 } else { -FRAC_PI_2 },
+                },
+                Segment::Line {
+                    start: arc_end,
+                    end: (arc_end.0 + out_x * run_out, arc_end.1 + out_y * run_out),
+                },
+            ],
+            exit_lane,
+        }
+    }
+
+    /// Pose after travelling `distance` along the path; past the end the last
+    /// segment carries on in a straight line
+    pub fn pose_at(&self, distance: f32) -> Pose {
+        let mut remaining = distance;
+        for segment in &self.segments[..self.segments.len() - 1] {
+            let length = segment.length();
+            if remaining < length {
+                return segment.pose_at(remaining);
+            }
+            remaining -= length;
+        }
+        self.segments[self.segments.len() - 1].pose_at(remaining)
+    }
+
+    /// Start and end of the turning arc, as distances along the path
+    pub fn turn_span(&self) -> Option<(f32, f32)> {
+        let mut start = 0.0;
+        for segment in &self.segments {
+            let length = segment.length();
+            if let Segment::Arc { .. } = segment {
+                return Some((start, start + length));
+            }
+            start += length;
+        }
+        None
+    }
+}
+
+fn unit(direction: Direction) -> (f32, f32) {
+    match direction {
+        Direction::North => (0.0, -1.0),
+        Direction::South => (0.0, 1.0),
+        Direction::East => (1.0, 0.0),
+        Direction::West => (-1.0, 0.0),
+    }
+}
+
+/// Where a vehicle travelling in `direction` on `lane` enters the screen
+fn start_position(direction: Direction, lane: i32) -> (f32, f32) {
+    match direction {
+        Direction::North => (vertical_lane_center(lane), WINDOW_HEIGHT as f32),
+        Direction::South => (vertical_lane_center(lane), 0.0),
+        Direction::East => (0.0, horizontal_lane_center(lane)),
+        Direction::West => (WINDOW_WIDTH as f32, horizontal_lane_center(lane)),
+    }
+}
+
+/// Distance from `point` to the edge of the intersection a vehicle travelling
+/// in `direction` crosses on the way in, or on the way out if `leaving`
+fn room_to_edge(point: (f32, f32), direction: Direction, leaving: bool) -> f32 {
+    let (x, y) = point;
+    let (west, east, north, south) = config().intersection();
+    match (direction, leaving) {
+        (Direction::North, false) | (Direction::South, true) => south - y,
+        (Direction::South, false) | (Direction::North, true) => y - north,
+        (Direction::East, false) | (Direction::West, true) => x - west,
+        (Direction::West, false) | (Direction::East, true) => east - x,
+    }
+}
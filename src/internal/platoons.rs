@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use super::car_following::{leader, Gipps};
+use super::config::config;
+use super::conflicts::{conflict_matrix, ConflictMatrix};
+use super::constants::*;
+use super::intersection::{decision_distance, still_crossing, stop_at_box, RouteFootprints};
+use super::policy::IntersectionPolicy;
+use super::vehicles::{Direction, Route, SpeedTier, Vehicle};
+
+/// An inbound lane, as (direction of travel, lane)
+type Lane = (Direction, i32);
+
+/// Batch scheduler letting platoons through the box one batch at a time.
+///
+/// Vehicles within `PLATOON_RANGE` of the box queue up by inbound lane, and the
+/// vehicles at the front of a lane that follow each other closely form a
+/// platoon. Whenever the box is empty, a batch is put together from the
+/// platoons of lanes whose routes never meet, up to `platoons.max_batch`
+/// vehicles, and only that batch may enter until all of it has left the box. It
+/// picks the batch that, followed by the best batch formed from what is left
+/// queued, keeps the queued vehicles waiting least in total: each vehicle waits
+/// for the occupancy of every batch let in before its own, and those in neither
+/// batch wait for both. A lane passed over `platoons.max_skipped` times in a
+/// row while it has vehicles queued leads the next batch, unless other lanes
+/// have been passed over longer: those lead first, and lanes passed over most
+/// get the first places in every batch, so no approach starves.
+pub struct PlatoonScheduler {
+    max_batch: usize,
+    max_skipped: u32,
+    /// Vehicles in the current batch that have not yet left the box
+    batch: HashMap<u32, Route>,
+    /// Batches formed in a row without each lane while it had vehicles queued
+    skipped: HashMap<Lane, u32>,
+    footprints: RouteFootprints,
+    conflicts: &'static ConflictMatrix,
+    car_following: Gipps,
+}
+
+impl PlatoonScheduler {
+    pub fn new() -> Self {
+        let platoons = &config().platoons;
+        PlatoonScheduler {
+            max_batch: platoons.max_batch,
+            max_skipped: platoons.max_skipped,
+            batch: HashMap::new(),
+            skipped: HashMap::new(),
+            footprints: RouteFootprints::new(),
+            conflicts: conflict_matrix(),
+            car_following: Gipps::default(),
+        }
+    }
+
+    /// Batches formed in a row without `lane` while it had vehicles queued
+    fn skipped(&self, lane: &Lane) -> u32 {
+        self.skipped.get(lane).copied().unwrap_or(0)
+    }
+
+    /// Let the best batch that can be formed from `vehicles` into the box
+    fn form_batch(&mut self, vehicles: &[Vehicle]) {
+        let queues = platoons(vehicles);
+        if queues.is_empty() {
+            return;
+        }
+
+        // A starving lane leads the batch; otherwise every lane is tried as
+        // the lead
+        let starving = queues
+            .iter()
+            .enumerate()
+            .map(|(i, (lane, _))| (i, self.skipped(lane)))
+            .filter(|&(_, skipped)| skipped >= self.max_skipped)
+            .max_by_key(|&(i, skipped)| (skipped, std::cmp::Reverse(i)));
+        let leads: Vec<usize> = match starving {
+            Some((i, _)) => vec![i],
+            None => (0..queues.len()).collect(),
+        };
+
+        let queued: usize = queues.iter().map(|(_, platoon)| platoon.len()).sum();
+        let mut best: Option<(f32, Vec<&Vehicle>)> = None;
+        for lead in leads {
+            let batch = self.batch_led_by(lead, &queues);
+            let waiting = queued - batch.len();
+            let delay = waiting as f32 * occupancy(&batch) + self.least_delay(&batch, &queues, waiting);
+            if best.as_ref().is_none_or(|(best_delay, _)| delay < *best_delay) {
+                best = Some((delay, batch));
+            }
+        }
+
+        let Some((_, batch)) = best else {
+            return;
+        };
+        self.skipped.retain(|lane, _| queues.iter().any(|(queued, _)| queued == lane));
+        for (lane, _) in &queues {
+            let served = batch
+                .iter()
+                .any(|vehicle| (vehicle.route.direction, vehicle.route.lane) == *lane);
+            let skipped = self.skipped.entry(*lane).or_insert(0);
+            *skipped = if served { 0 } else { *skipped + 1 };
+        }
+        self.batch = batch.iter().map(|vehicle| (vehicle.id, vehicle.route)).collect();
+    }
+
+    /// Total wait the best batch to follow `first` puts on the `waiting`
+    /// vehicles `first` leaves queued
+    fn least_delay(&self, first: &[&Vehicle], queues: &[(Lane, Vec<&Vehicle>)], waiting: usize) -> f32 {
+        let left: Vec<(Lane, Vec<&Vehicle>)> = queues
+            .iter()
+            .map(|(lane, platoon)| {
+                let platoon = platoon
+                    .iter()
+                    .filter(|vehicle| first.iter().all(|member| member.id != vehicle.id))
+                    .copied()
+                    .collect();
+                (*lane, platoon)
+            })
+            .filter(|(_, platoon): &(Lane, Vec<&Vehicle>)| !platoon.is_empty())
+            .collect();
+
+        (0..left.len())
+            .map(|lead| {
+                let batch = self.batch_led_by(lead, &left);
+                (waiting - batch.len()) as f32 * occupancy(&batch)
+            })
+            .min_by(f32::total_cmp)
+            .unwrap_or(0.0)
+    }
+
+    /// The platoon of lane `lead`, then the platoons of the other lanes, those
+    /// passed over most first, as far as they fit and never meet any route
+    /// already in the batch
+    fn batch_led_by<'a>(&self, lead: usize, queues: &[(Lane, Vec<&'a Vehicle>)]) -> Vec<&'a Vehicle> {
+        let mut batch: Vec<&Vehicle> = queues[lead].1.iter().take(self.max_batch).copied().collect();
+        let mut others: Vec<usize> = (0..queues.len()).filter(|&i| i != lead).collect();
+        others.sort_by_key(|&i| std::cmp::Reverse(self.skipped(&queues[i].0)));
+        for platoon in others.into_iter().map(|i| &queues[i].1) {
+            // Vehicles in one lane follow each other, so a lane joins in front
+            // to back order and stops at the first that does not fit
+            for vehicle in platoon {
+                let fits = batch.len() < self.max_batch
+                    && batch
+                        .iter()
+                        .all(|member| !self.conflicts.conflicts(vehicle.route, member.route));
+                if !fits {
+                    break;
+                }
+                batch.push(vehicle);
+            }
+        }
+        batch
+    }
+}
+
+impl IntersectionPolicy for PlatoonScheduler {
+    fn name(&self) -> &'static str {
+        "platoon"
+    }
+
+    fn held_tiles(&self) -> Vec<(u32, (i32, i32))> {
+        self.footprints.held(&self.batch)
+    }
+
+    fn update(&mut self, vehicles: &[Vehicle], dt: f32) -> Vec<f32> {
+        self.batch.retain(|&id, _| still_crossing(vehicles, id));
+        if self.batch.is_empty() {
+            self.form_batch(vehicles);
+        }
+
+        vehicles
+            .iter()
+            .map(|vehicle| {
+                // Queue behind the vehicle ahead in the same lane
+                let mut target = self.car_following.velocity(
+                    vehicle,
+                    SpeedTier::Medium.velocity(),
+                    leader(vehicle, vehicles),
+                );
+
+                // Anyone outside the batch brakes to a stop at the edge of the box
+                if let Some(distance) = decision_distance(vehicle, dt) {
+                    if !self.batch.contains_key(&vehicle.id) {
+                        target = target.min(stop_at_box(vehicle, distance));
+                    }
+                }
+
+                target
+            })
+            .collect()
+    }
+}
+
+impl Default for PlatoonScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The platoon at the front of each inbound lane with vehicles queued: the
+/// front vehicle and those following it within `PLATOON_GAP`, front first.
+/// Lanes come in the order their vehicles first appear in `vehicles`.
+fn platoons(vehicles: &[Vehicle]) -> Vec<(Lane, Vec<&Vehicle>)> {
+    let mut queues: Vec<(Lane, Vec<(f32, &Vehicle)>)> = Vec::new();
+    for vehicle in vehicles {
+        let Some(distance) = vehicle.distance_to_intersection() else {
+            continue;
+        };
+        if distance > PLATOON_RANGE {
+            continue;
+        }
+        let lane = (vehicle.route.direction, vehicle.route.lane);
+        match queues.iter_mut().find(|(queued, _)| *queued == lane) {
+            Some((_, queue)) => queue.push((distance, vehicle)),
+            None => queues.push((lane, vec![(distance, vehicle)])),
+        }
+    }
+
+    queues
+        .into_iter()
+        .map(|(lane, mut queue)| {
+            queue.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut platoon = vec![queue[0].1];
+            for pair in queue.windows(2) {
+                let ((ahead, vehicle_ahead), (behind, vehicle)) = (pair[0], pair[1]);
+                let gap = behind - ahead - vehicle_ahead.length;
+                if gap > PLATOON_GAP {
+                    break;
+                }
+                platoon.push(vehicle);
+            }
+            (lane, platoon)
+        })
+        .collect()
+}
+
+/// Seconds until the last vehicle of `batch` has left the box at cruising
+/// speed
+fn occupancy(batch: &[&Vehicle]) -> f32 {
+    let (west, east, north, south) = config().intersection();
+    let span = (east - west).max(south - north);
+    batch
+        .iter()
+        .map(|vehicle| {
+            let distance = vehicle.distance_to_intersection().unwrap_or(0.0);
+            (distance + span + vehicle.length) / SpeedTier::Medium.velocity()
+        })
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::internal::intersection::tests::drive;
+
+    use super::*;
+
+    #[test]
+    fn keeps_vehicles_apart_and_lets_every_one_through() {
+        for seed in 1..=2 {
+            drive("platoon", seed, false);
+        }
+    }
+
+    #[test]
+    fn lanes_passed_over_longest_lead() {
+        let inbound_lanes = 4 * config().roads.lanes_per_direction as u32;
+        for seed in 1..=3 {
+            let mut scheduler = PlatoonScheduler::new();
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut vehicles: Vec<Vehicle> = Vec::new();
+            for tick in 0..(60.0 / SIMULATION_DT) as u32 {
+                if tick % 10 == 0 {
+                    let mut vehicle = Vehicle::random(&mut rng);
+                    if vehicle.has_room(&vehicles) {
+                        vehicle.id = tick;
+                        vehicles.push(vehicle);
+                    }
+                }
+
+                let forming = scheduler.batch.is_empty();
+                let skipped = scheduler.skipped.clone();
+                let targets = scheduler.update(&vehicles, SIMULATION_DT);
+                for (vehicle, target) in vehicles.iter_mut().zip(targets) {
+                    vehicle.move_vehicle(target, SIMULATION_DT);
+                }
+                vehicles.retain(Vehicle::is_on_screen);
+
+                // Once a lane has been passed over `max_skipped` times, one of
+                // the lanes passed over longest leads the next batch
+                let longest = skipped.values().copied().max().unwrap_or(0);
+                if forming && !scheduler.batch.is_empty() && longest >= scheduler.max_skipped {
+                    assert!(skipped
+                        .iter()
+                        .any(|(lane, &times)| times == longest && scheduler.skipped(lane) == 0));
+                }
+                // Lanes passed over that often lead in the order they got
+                // there, so none waits behind more than every other lane
+                for &times in scheduler.skipped.values() {
+                    assert!(times < scheduler.max_skipped + inbound_lanes);
+                }
+            }
+        }
+    }
+}
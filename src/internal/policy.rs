@@ -0,0 +1,80 @@
+use super::car_following::{leader, Gipps};
+use super::intersection::{Admission, IntersectionManager};
+use super::platoons::PlatoonScheduler;
+use super::reservation::ReservationScheduler;
+use super::signals::{PhaseOrder, Signal};
+use super::vehicles::{SpeedTier, Vehicle};
+
+/// Strategy deciding how fast each vehicle may approach and cross the box.
+/// The simulation asks it once per tick, so policies can be swapped and
+/// benchmarked against each other on the same seeded traffic.
+pub trait IntersectionPolicy {
+    /// Name it is chosen by with `--policy`
+    fn name(&self) -> &'static str;
+
+    /// Target velocity of each vehicle, in the order given, for the next step
+    /// of `dt` seconds
+    fn update(&mut self, vehicles: &[Vehicle], dt: f32) -> Vec<f32>;
+
+    /// Intersection tiles held by each vehicle, as (vehicle id, tile)
+    fn held_tiles(&self) -> Vec<(u32, (i32, i32))> {
+        Vec::new()
+    }
+
+    /// Forget any go-ahead given to `vehicle`, which is being held back or
+    /// waved through to make way for an emergency vehicle
+    fn withdraw(&mut self, _vehicle: u32) {}
+}
+
+/// Every policy that can be chosen with `--policy`; the first is the default
+pub const POLICIES: [&str; 7] = [
+    "reservation",
+    "conflict",
+    "fcfs",
+    "traffic-light",
+    "longest-queue",
+    "platoon",
+    "none",
+];
+
+/// The policy called `name`, one of `POLICIES`
+pub fn policy_named(name: &str) -> Option<Box<dyn IntersectionPolicy>> {
+    let policy: Box<dyn IntersectionPolicy> = match name {
+        "reservation" => Box::new(ReservationScheduler::new()),
+        "conflict" => Box::new(IntersectionManager::new()),
+        "fcfs" => Box::new(IntersectionManager::with_admission(Admission::ArrivalOrder)),
+        "traffic-light" => Box::new(IntersectionManager::with_admission(Admission::Signal(Signal::new(
+            PhaseOrder::Rotation,
+        )))),
+        "longest-queue" => Box::new(IntersectionManager::with_admission(Admission::Signal(Signal::new(
+            PhaseOrder::LongestQueue,
+        )))),
+        "platoon" => Box::new(PlatoonScheduler::new()),
+        "none" => Box::new(NoControl::default()),
+        _ => return None,
+    };
+    Some(policy)
+}
+
+/// Baseline without any intersection control: vehicles only keep their
+/// distance to the vehicle ahead, and collide in the box
+#[derive(Default)]
+pub struct NoControl {
+    car_following: Gipps,
+}
+
+impl IntersectionPolicy for NoControl {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn update(&mut self, vehicles: &[Vehicle], _dt: f32) -> Vec<f32> {
+        vehicles
+            .iter()
+            .map(|vehicle| {
+                self.car_following
+                    .velocity(vehicle, SpeedTier::Medium.velocity(), leader(vehicle, vehicles))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,351 @@
+use serde::de::{value, DeserializeOwned, IntoDeserializer};
+
+use super::config::{config, RoadConfig};
+use super::policy::{policy_named, POLICIES};
+use super::simulation::{Input, Simulation, Spawn};
+use super::vehicles::Route;
+
+const HEADER: &str = "01-road recording";
+/// Bumped with every change to the file format or to how a seed plays out
+const VERSION: u32 = 1;
+
+/// Everything needed to run a simulation again exactly as it went: the seed,
+/// intersection policy and configuration it started with, and every user input with
+/// the tick it was applied after. Spawns are kept too, so a replay can tell
+/// when it no longer matches the original run.
+///
+/// Saved as plain text, one line per input or spawn:
+///
+/// ```text
+/// 01-road recording 1
+/// seed 42
+/// policy reservation
+/// ticks 3600
+/// config 100
+/// ...100 lines of TOML...
+/// input 120 spawn North
+/// input 240 spawn-emergency
+/// input 300 random-spawning
+/// spawn 125 7 North 5 Left Truck
+/// spawn 245 9 West 2 Straight Car emergency
+/// ```
+///
+/// Spawn lanes are numbered from 1, as in the config.
+pub struct Recording {
+    pub seed: u64,
+    pub policy: String,
+    pub config: RoadConfig,
+    pub inputs: Vec<(u64, Input)>,
+    pub spawns: Vec<Spawn>,
+    /// Length of the run
+    pub ticks: u64,
+}
+
+impl Recording {
+    /// Start recording a simulation that has not been stepped yet
+    pub fn start(seed: u64, simulation: &Simulation) -> Self {
+        Recording {
+            seed,
+            policy: simulation.policy().name().to_string(),
+            config: config().clone(),
+            inputs: Vec::new(),
+            spawns: Vec::new(),
+            ticks: 0,
+        }
+    }
+
+    /// Take the spawns and length of the run so far from the simulation
+    pub fn update(&mut self, simulation: &mut Simulation) {
+        self.spawns.append(&mut simulation.spawns);
+        self.ticks = simulation.tick;
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_text()).map_err(|e| format!("Cannot write recording {}: {}", path, e))
+    }
+
+    fn to_text(&self) -> String {
+        let config = self.config.to_toml();
+        let mut lines = vec![
+            format!("{} {}", HEADER, VERSION),
+            format!("seed {}", self.seed),
+            format!("policy {}", self.policy),
+            format!("ticks {}", self.ticks),
+            format!("config {}", config.lines().count()),
+        ];
+        lines.extend(config.lines().map(str::to_string));
+        for (tick, input) in &self.inputs {
+            lines.push(match input {
+                Input::Spawn(direction) => format!("input {} spawn {:?}", tick, direction),
+                Input::SpawnEmergency => format!("input {} spawn-emergency", tick),
+                Input::ToggleRandomSpawning => format!("input {} random-spawning", tick),
+            });
+        }
+        for spawn in &self.spawns {
+            let route = spawn.route;
+            lines.push(format!(
+                "spawn {} {} {:?} {} {:?} {:?}{}",
+                spawn.tick,
+                spawn.id,
+                route.direction,
+                route.lane + 1,
+                route.turn_direction,
+                spawn.vehicle_type,
+                if spawn.emergency { " emergency" } else { "" }
+            ));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    pub fn load(path: &str) -> Result<Recording, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read recording {}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("Invalid recording {}: {}", path, e))
+    }
+
+    fn parse(text: &str) -> Result<Recording, String> {
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+        let version = lines
+            .next()
+            .and_then(|(_, line)| line.strip_prefix(HEADER))
+            .and_then(|rest| rest.trim().parse::<u32>().ok())
+            .ok_or(format!("expected \"{} <version>\" on the first line", HEADER))?;
+        if version != VERSION {
+            return Err(format!(
+                "recorded in format version {}, but this build only replays version {}",
+                version, VERSION
+            ));
+        }
+
+        let mut header = |key: &str| -> Result<String, String> {
+            let (number, line) = lines.next().ok_or(format!("missing {}", key))?;
+            line.strip_prefix(key)
+                .and_then(|rest| rest.strip_prefix(' '))
+                .map(str::to_string)
+                .ok_or(format!("line {}: expected {}", number, key))
+        };
+        let seed = parse_number(&header("seed")?)?;
+        let policy = header("policy")?;
+        if !POLICIES.contains(&policy.as_str()) {
+            return Err(format!("unknown policy {:?}", policy));
+        }
+        let ticks = parse_number(&header("ticks")?)?;
+        let config_lines = parse_number(&header("config")?)? as usize;
+
+        let config_text: Vec<&str> = lines.by_ref().take(config_lines).map(|(_, line)| line).collect();
+        if config_text.len() < config_lines {
+            return Err("config ends early".to_string());
+        }
+        let config = RoadConfig::from_toml(&config_text.join("\n")).map_err(|e| format!("config: {}", e))?;
+
+        let mut inputs = Vec::new();
+        let mut spawns = Vec::new();
+        for (number, line) in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let event = match fields.as_slice() {
+                [] => Ok(()),
+                ["input", tick, "spawn", direction] => {
+                    parse_name(direction).and_then(|direction| {
+                        inputs.push((parse_number(tick)?, Input::Spawn(direction)));
+                        Ok(())
+                    })
+                }
+                ["input", tick, "spawn-emergency"] => parse_number(tick).map(|tick| {
+                    inputs.push((tick, Input::SpawnEmergency));
+                }),
+                ["input", tick, "random-spawning"] => parse_number(tick).map(|tick| {
+                    inputs.push((tick, Input::ToggleRandomSpawning));
+                }),
+                ["spawn", tick, id, direction, lane, turn_direction, vehicle_type, kind @ ..] => (|| {
+                    let emergency = match kind {
+                        [] => false,
+                        ["emergency"] => true,
+                        _ => return Err(format!("unexpected {:?}", line)),
+                    };
+                    spawns.push(Spawn {
+                        tick: parse_number(tick)?,
+                        id: parse_number(id)? as u32,
+                        route: Route {
+                            direction: parse_name(direction)?,
+                            lane: parse_number(lane)? as i32 - 1,
+                            turn_direction: parse_name(turn_direction)?,
+                        },
+                        vehicle_type: parse_name(vehicle_type)?,
+                        emergency,
+                    });
+                    Ok(())
+                })(),
+                _ => Err(format!("unexpected {:?}", line)),
+            };
+            event.map_err(|e| format!("line {}: {}", number, e))?;
+        }
+
+        Ok(Recording {
+            seed,
+            policy,
+            config,
+            inputs,
+            spawns,
+            ticks,
+        })
+    }
+}
+
+/// Plays a recording back into a simulation, applying each input after the
+/// same tick as in the original run. Time only runs forward in the
+/// simulation, so seeking backwards starts again from the beginning.
+pub struct Replay {
+    pub recording: Recording,
+    next_input: usize,
+    checked_spawns: usize,
+    diverged: bool,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Replay {
+            recording,
+            next_input: 0,
+            checked_spawns: 0,
+            diverged: false,
+        }
+    }
+
+    /// A fresh simulation at the start of the recording
+    pub fn restart(&mut self) -> Simulation {
+        let policy = policy_named(&self.recording.policy).expect("policy checked when loaded");
+        let mut simulation = Simulation::new(policy, self.recording.seed);
+        self.next_input = 0;
+        self.checked_spawns = 0;
+        self.apply_inputs(&mut simulation);
+        simulation
+    }
+
+    pub fn finished(&self, simulation: &Simulation) -> bool {
+        simulation.tick >= self.recording.ticks
+    }
+
+    pub fn step(&mut self, simulation: &mut Simulation) {
+        simulation.step();
+        self.apply_inputs(simulation);
+    }
+
+    /// Bring the simulation to `tick`, clamped to the length of the recording
+    pub fn seek(&mut self, simulation: &mut Simulation, tick: u64) {
+        let tick = tick.min(self.recording.ticks);
+        if tick < simulation.tick {
+            let mut restarted = self.restart();
+            restarted.take_listeners(simulation);
+            *simulation = restarted;
+        }
+        while simulation.tick < tick {
+            self.step(simulation);
+        }
+    }
+
+    fn apply_inputs(&mut self, simulation: &mut Simulation) {
+        while let Some(&(tick, input)) = self.recording.inputs.get(self.next_input) {
+            if tick > simulation.tick {
+                break;
+            }
+            simulation.apply(input);
+            self.next_input += 1;
+        }
+        self.check_spawns(simulation);
+    }
+
+    /// Warn once if the simulation spawns anything the original run did not
+    fn check_spawns(&mut self, simulation: &mut Simulation) {
+        for spawn in simulation.spawns.drain(..) {
+            let expected = self.recording.spawns.get(self.checked_spawns);
+            self.checked_spawns += 1;
+            if expected != Some(&spawn) && !self.diverged {
+                self.diverged = true;
+                eprintln!(
+                    "Replay diverged at tick {}: spawned {:?}, recording has {:?}",
+                    spawn.tick, spawn, expected
+                );
+            }
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Result<u64, String> {
+    text.parse().map_err(|_| format!("expected a number, got {:?}", text))
+}
+
+/// Name of a variant of one of the route or vehicle enums, as they
+/// deserialize from the config
+fn parse_name<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    T::deserialize(text.into_deserializer()).map_err(|e: value::Error| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::vehicles::Direction;
+
+    /// A short run with one input of each kind
+    fn record() -> Recording {
+        let mut simulation = Simulation::new(policy_named("conflict").unwrap(), 42);
+        let mut recording = Recording::start(42, &simulation);
+        for tick in 0..600 {
+            let input = match tick {
+                60 => Some(Input::Spawn(Direction::West)),
+                120 => Some(Input::SpawnEmergency),
+                300 => Some(Input::ToggleRandomSpawning),
+                _ => None,
+            };
+            if let Some(input) = input {
+                simulation.apply(input);
+                recording.inputs.push((simulation.tick, input));
+            }
+            simulation.step();
+            recording.update(&mut simulation);
+        }
+        recording
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let recording = record();
+        let parsed = Recording::parse(&recording.to_text()).unwrap();
+
+        assert_eq!(parsed.seed, recording.seed);
+        assert_eq!(parsed.policy, recording.policy);
+        assert_eq!(parsed.ticks, recording.ticks);
+        assert_eq!(parsed.config.to_toml(), recording.config.to_toml());
+        assert_eq!(parsed.inputs, recording.inputs);
+        assert_eq!(parsed.spawns, recording.spawns);
+        assert!(recording.spawns.iter().any(|spawn| spawn.emergency));
+    }
+
+    #[test]
+    fn replay_spawns_what_was_recorded() {
+        let recording = record();
+        let recorded = recording.spawns.len();
+        let mut replay = Replay::new(recording);
+        let mut simulation = replay.restart();
+        while !replay.finished(&simulation) {
+            replay.step(&mut simulation);
+        }
+
+        assert!(!replay.diverged);
+        assert_eq!(replay.checked_spawns, recorded);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let header = format!("{} {}", HEADER, VERSION);
+        let text = record().to_text().replacen(&header, &format!("{} {}", HEADER, VERSION + 1), 1);
+        let error = Recording::parse(&text).err().unwrap();
+        assert!(error.contains(&format!("format version {}", VERSION + 1)), "{}", error);
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        let text = format!("{}input 5 spawn Up\n", record().to_text());
+        let error = Recording::parse(&text).err().unwrap();
+        assert!(error.contains("unknown variant"), "{}", error);
+    }
+}
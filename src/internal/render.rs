@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use sdl2::{
+    event::Event,
+    image::LoadTexture,
+    keyboard::Keycode,
+    pixels::Color,
+    rect::{Point, Rect},
+    render::{Canvas, Texture, TextureCreator},
+    ttf::Sdl2TtfContext,
+    video::{Window, WindowContext},
+    EventPump, Sdl,
+};
+
+use super::constants::*;
+use super::environment::draw_env;
+use super::geometry::{oriented_rect, polygon_bounds};
+use super::simulation::Simulation;
+use super::statistics::Statistics;
+use super::text::{FontSource, TextCache};
+use super::vehicles::{TurnDirection, Vehicle, VehicleType};
+
+/// Car textures, one per entry of `CAR_SPRITES`; `None` where the image could
+/// not be loaded, so those vehicles are drawn as plain rectangles
+pub struct Sprites<'a> {
+    cars: Vec<Option<Texture<'a>>>,
+}
+
+impl<'a> Sprites<'a> {
+    pub fn load(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let cars = CAR_SPRITES
+            .iter()
+            .map(|path| {
+                texture_creator
+                    .load_texture(path)
+                    .map_err(|e| eprintln!("Error loading car sprite {}: {}", path, e))
+                    .ok()
+            })
+            .collect();
+        Sprites { cars }
+    }
+}
+
+/// Draw the road and every vehicle of the simulation, with vehicles placed
+/// `alpha` (0-1) of the way between the last two simulation steps
+pub fn draw_simulation(
+    canvas: &mut Canvas<Window>,
+    text: &mut TextCache,
+    simulation: &Simulation,
+    sprites: &Sprites,
+    alpha: f32,
+) {
+    // Clear screen
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+
+    // Draw environment
+    draw_env(canvas, text);
+
+    for vehicle in &simulation.vehicles {
+        draw_vehicle(canvas, vehicle, sprites, alpha, simulation.time);
+    }
+}
+
+/// Progress bar along the bottom of the window showing how far into a
+/// replay of `ticks` steps the simulation is
+pub fn draw_timeline(canvas: &mut Canvas<Window>, tick: u64, ticks: u64) {
+    let top = (WINDOW_HEIGHT - TIMELINE_HEIGHT) as i32;
+    let played = if ticks == 0 { 1.0 } else { tick as f64 / ticks as f64 };
+    let played_width = (played.min(1.0) * WINDOW_WIDTH as f64) as u32;
+
+    canvas.set_draw_color(Color::RGB(60, 60, 60));
+    canvas
+        .fill_rect(Rect::new(0, top, WINDOW_WIDTH, TIMELINE_HEIGHT))
+        .unwrap_or_else(|e| eprintln!("Error drawing timeline: {}", e));
+    if played_width > 0 {
+        canvas.set_draw_color(Color::RGB(220, 180, 40));
+        canvas
+            .fill_rect(Rect::new(0, top, played_width, TIMELINE_HEIGHT))
+            .unwrap_or_else(|e| eprintln!("Error drawing timeline: {}", e));
+    }
+}
+
+/// Draw one vehicle; `time` in simulated seconds times the flashing of an
+/// emergency vehicle's lights
+pub fn draw_vehicle(canvas: &mut Canvas<Window>, vehicle: &Vehicle, sprites: &Sprites, alpha: f32, time: f64) {
+    let (pos_x, pos_y, heading) = vehicle.interpolated_pose(alpha);
+
+    draw_body(canvas, vehicle, sprites, (pos_x, pos_y, heading));
+    if vehicle.emergency {
+        draw_light_bar(canvas, vehicle, (pos_x, pos_y, heading), time);
+    }
+}
+
+/// Cars use their sprite; the other types, and cars whose sprite is missing,
+/// are drawn from shapes in the colour of their turn
+fn draw_body(
+    canvas: &mut Canvas<Window>,
+    vehicle: &Vehicle,
+    sprites: &Sprites,
+    (pos_x, pos_y, heading): (f32, f32, f32),
+) {
+    if vehicle.vehicle_type == VehicleType::Car {
+        if let Some(Some(texture)) = sprites.cars.get(vehicle.model) {
+            // Sprites face east, so the heading is the clockwise rotation to apply
+            let rect = Rect::new(
+                (pos_x - vehicle.length / 2.0).round() as i32,
+                (pos_y - vehicle.width / 2.0).round() as i32,
+                vehicle.length.round() as u32,
+                vehicle.width.round() as u32,
+            );
+            canvas
+                .copy_ex(texture, None, rect, heading.to_degrees() as f64, None, false, false)
+                .unwrap_or_else(|e| {
+                    eprintln!("Error rendering vehicle: {}", e);
+                });
+            return;
+        }
+    }
+
+    let color = match vehicle.turn_direction {
+        TurnDirection::Left => Color::RGB(255, 100, 100),    // Red
+        TurnDirection::Straight => Color::RGB(100, 255, 100), // Green
+        TurnDirection::Right => Color::RGB(100, 100, 255),   // Blue
+    };
+    let dark = Color::RGB(50, 50, 50);
+    let pose = (pos_x, pos_y, heading);
+    let (length, width) = (vehicle.length, vehicle.width);
+
+    // Each part as (colour, offset forward of the centre, length, width)
+    let parts = match vehicle.vehicle_type {
+        VehicleType::Car => vec![(color, 0.0, length, width)],
+        // Trailer behind a separate dark cab
+        VehicleType::Truck => vec![
+            (color, -length * 0.1, length * 0.8, width),
+            (dark, length * 0.4, length * 0.18, width * 0.9),
+        ],
+        // One long body with a strip of windows down each side
+        VehicleType::Bus => vec![
+            (color, 0.0, length, width),
+            (dark, 0.0, length * 0.85, width * 0.7),
+            (color, 0.0, length * 0.85, width * 0.4),
+        ],
+        // Narrow bike with the rider sitting just behind the middle
+        VehicleType::Motorcycle => vec![
+            (color, 0.0, length, width),
+            (dark, -length * 0.1, length * 0.3, width),
+        ],
+    };
+    for (color, forward, length, width) in parts {
+        canvas.set_draw_color(color);
+        fill_polygon(canvas, &part(pose, forward, length, width)).unwrap_or_else(|e| {
+            eprintln!("Error rendering vehicle: {}", e);
+        });
+    }
+}
+
+/// Rectangle `length` by `width` lined up with a vehicle at `pose`, its centre
+/// `forward` ahead of the vehicle's
+fn part((pos_x, pos_y, heading): (f32, f32, f32), forward: f32, length: f32, width: f32) -> [(f32, f32); 4] {
+    oriented_rect(
+        pos_x + forward * heading.cos(),
+        pos_y + forward * heading.sin(),
+        heading,
+        length / 2.0,
+        width / 2.0,
+    )
+}
+
+/// Red and blue lights across the roof that swap sides every
+/// `EMERGENCY_FLASH` seconds
+fn draw_light_bar(
+    canvas: &mut Canvas<Window>,
+    vehicle: &Vehicle,
+    (pos_x, pos_y, heading): (f32, f32, f32),
+    time: f64,
+) {
+    let (red, blue) = (Color::RGB(255, 40, 40), Color::RGB(40, 90, 255));
+    let flashes = (time / EMERGENCY_FLASH as f64) as u64;
+    let colors = if flashes % 2 == 0 {
+        [red, blue]
+    } else {
+        [blue, red]
+    };
+
+    // One light either side of the centre line
+    let (side_x, side_y) = (-heading.sin(), heading.cos());
+    let offset = vehicle.width / 4.0;
+    for (side, color) in [-1.0, 1.0].into_iter().zip(colors) {
+        let light = oriented_rect(
+            pos_x + side * offset * side_x,
+            pos_y + side * offset * side_y,
+            heading,
+            vehicle.length / 8.0,
+            vehicle.width / 4.0,
+        );
+        canvas.set_draw_color(color);
+        fill_polygon(canvas, &light).unwrap_or_else(|e| {
+            eprintln!("Error rendering light bar: {}", e);
+        });
+    }
+}
+
+/// Fill a convex polygon one pixel row at a time with the current draw colour
+fn fill_polygon(canvas: &mut Canvas<Window>, polygon: &[(f32, f32)]) -> Result<(), String> {
+    let (_, top, _, bottom) = polygon_bounds(polygon);
+    for row in top.round() as i32..bottom.round() as i32 {
+        // Sample through the middle of the row
+        let y = row as f32 + 0.5;
+        let crossings = polygon
+            .iter()
+            .zip(polygon.iter().cycle().skip(1))
+            .filter(|(start, end)| (start.1 <= y) != (end.1 <= y))
+            .map(|(start, end)| start.0 + (y - start.1) * (end.0 - start.0) / (end.1 - start.1));
+        let (left, right) = crossings.fold((f32::MAX, f32::MIN), |(left, right), x| (left.min(x), right.max(x)));
+        if left < right {
+            canvas.draw_line(
+                Point::new(left.round() as i32, row),
+                Point::new(right.round() as i32 - 1, row),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Open a window with the run statistics and wait until it is closed
+pub fn show_statistics(
+    sdl_context: &Sdl,
+    event_pump: &mut EventPump,
+    ttf: &Sdl2TtfContext,
+    font: &FontSource,
+    statistics: &Statistics,
+) {
+    let video_subsystem = sdl_context.video().unwrap();
+    let window = video_subsystem
+        .window("01-road statistics", STATS_WINDOW_WIDTH, STATS_WINDOW_HEIGHT)
+        .position_centered()
+        .build()
+        .unwrap();
+    let mut canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let mut text = TextCache::new(ttf, font.clone(), &texture_creator);
+
+    canvas.set_draw_color(Color::RGB(30, 30, 30));
+    canvas.clear();
+    for (i, line) in statistics.summary().iter().enumerate() {
+        text.draw(&mut canvas, line, 20, Color::WHITE, (20, 20 + i as i32 * 32), None)
+            .unwrap_or_else(|e| eprintln!("Error drawing statistics: {}", e));
+    }
+    canvas.present();
+
+    'waiting: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::Window {
+                    win_event: sdl2::event::WindowEvent::Close,
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'waiting,
+                _ => {}
+            }
+        }
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / TICKS_PER_SECOND));
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::car_following::{leader, Gipps};
+use super::constants::*;
+use super::intersection::{covered_tiles, is_front_of_lane, stop_at_box};
+use super::policy::IntersectionPolicy;
+use super::vehicles::{SpeedTier, Vehicle};
+
+/// Vehicle id holding each tile, by tick
+type Slots = BTreeMap<u64, HashMap<(i32, i32), u32>>;
+
+/// Speeds a vehicle may ask to cross at, fastest first
+const REQUEST_TIERS: [SpeedTier; 3] = [SpeedTier::Fast, SpeedTier::Medium, SpeedTier::Slow];
+
+/// Tile/time-slot reservation scheduler in the style of AIM.
+///
+/// The front vehicle of each inbound lane, once it is close to the
+/// intersection, asks for every tile its path will cover at every future tick
+/// until it has crossed. The request is granted only if none of those slots is
+/// already held; otherwise the vehicle slows down and asks again a few ticks
+/// later.
+/// A vehicle holding a reservation targets exactly the speed tier it asked for
+/// through the box and cruises on from there, so it covers the slots it was
+/// planned to until it is a little way past the box.
+pub struct ReservationScheduler {
+    tick: u64,
+    /// Vehicle id holding each (tick, tile) slot
+    slots: Slots,
+    /// Target velocity and last planned tick of each vehicle with a reservation
+    granted: HashMap<u32, (f32, u64)>,
+    /// Tick from which each denied or withdrawn vehicle may ask again
+    retry_at: HashMap<u32, u64>,
+    car_following: Gipps,
+}
+
+impl ReservationScheduler {
+    pub fn new() -> Self {
+        ReservationScheduler {
+            tick: 0,
+            slots: BTreeMap::new(),
+            granted: HashMap::new(),
+            retry_at: HashMap::new(),
+            car_following: Gipps::default(),
+        }
+    }
+
+    /// Try to reserve the path of `vehicle` at each speed tier it can reach in
+    /// turn. A denied vehicle waits `REQUEST_RETRY` ticks before asking again.
+    fn request(&mut self, vehicle: &Vehicle, tick: u64, dt: f32) -> Option<f32> {
+        if self.retry_at.get(&vehicle.id).is_some_and(|&retry_at| tick < retry_at) {
+            return None;
+        }
+
+        for tier in REQUEST_TIERS {
+            let target = tier.velocity();
+            if target > vehicle.top_speed {
+                continue;
+            }
+            let Some(plan) = plan_slots(vehicle, target, tick, dt, &self.slots) else {
+                continue;
+            };
+            let last_tick = plan.last().map_or(tick, |&(slot_tick, _)| slot_tick);
+            for (slot_tick, tile) in plan {
+                self.slots.entry(slot_tick).or_default().insert(tile, vehicle.id);
+            }
+            self.granted.insert(vehicle.id, (target, last_tick));
+            self.retry_at.remove(&vehicle.id);
+            return Some(reserved_target(vehicle, target));
+        }
+
+        self.retry_at.insert(vehicle.id, tick + REQUEST_RETRY);
+        None
+    }
+}
+
+impl IntersectionPolicy for ReservationScheduler {
+    fn name(&self) -> &'static str {
+        "reservation"
+    }
+
+    /// Returns the target velocity of each vehicle for the next step of `dt`
+    /// seconds; every step is one tick of the reservation table
+    fn update(&mut self, vehicles: &[Vehicle], dt: f32) -> Vec<f32> {
+        let tick = self.tick;
+        self.tick += 1;
+
+        // Release finished or despawned reservations, and past slots
+        self.slots = self.slots.split_off(&(tick + 1));
+        self.granted.retain(|_, &mut (_, last_tick)| last_tick > tick);
+        let despawned: Vec<u32> = self
+            .granted
+            .keys()
+            .filter(|&&id| !vehicles.iter().any(|vehicle| vehicle.id == id))
+            .copied()
+            .collect();
+        for id in despawned {
+            self.withdraw(id);
+        }
+        self.retry_at.retain(|&id, _| vehicles.iter().any(|vehicle| vehicle.id == id));
+
+        let mut targets = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            if let Some(&(target, _)) = self.granted.get(&vehicle.id) {
+                targets.push(reserved_target(vehicle, target));
+                continue;
+            }
+
+            let mut target = SpeedTier::Medium.velocity();
+            if let Some(distance) = vehicle.distance_to_intersection() {
+                if distance <= RESERVATION_DISTANCE {
+                    // Only the front vehicle of a lane may ask, so vehicles with a
+                    // reservation never have a waiting vehicle in front of them
+                    if is_front_of_lane(vehicle, vehicles, |id| self.granted.contains_key(&id)) {
+                        if let Some(target) = self.request(vehicle, tick, dt) {
+                            targets.push(target);
+                            continue;
+                        }
+                    }
+                    // Denied: slow down, stop at the edge of the box and ask again
+                    target = SpeedTier::Slow
+                        .velocity()
+                        .min(stop_at_box(vehicle, distance));
+                }
+            }
+
+            // Queue behind the vehicle ahead in the same lane
+            targets.push(self.car_following.velocity(vehicle, target, leader(vehicle, vehicles)));
+        }
+
+        targets
+    }
+
+    /// Tiles reserved for the vehicles' current positions, that is for the
+    /// tick the last update moved them into
+    fn held_tiles(&self) -> Vec<(u32, (i32, i32))> {
+        self.slots
+            .get(&self.tick)
+            .into_iter()
+            .flat_map(|tiles| tiles.iter().map(|(&tile, &id)| (id, tile)))
+            .collect()
+    }
+
+    /// A reservation is only good for the ticks it was planned for, so a
+    /// vehicle driven off its plan loses it and asks again later
+    fn withdraw(&mut self, vehicle: u32) {
+        if let Some((_, last_tick)) = self.granted.remove(&vehicle) {
+            for tiles in self.slots.range_mut(..=last_tick).map(|(_, tiles)| tiles) {
+                tiles.retain(|_, id| *id != vehicle);
+            }
+            self.retry_at.insert(vehicle, self.tick + REQUEST_RETRY);
+        }
+    }
+}
+
+impl Default for ReservationScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Every (tick, tile) slot `vehicle` covers targeting `target` until it is
+/// `RESERVATION_EXIT` past the box, in steps of `dt` seconds; `None` as soon
+/// as one of them is already `taken`
+fn plan_slots(
+    vehicle: &Vehicle,
+    target: f32,
+    tick: u64,
+    dt: f32,
+    taken: &Slots,
+) -> Option<Vec<(u64, (i32, i32))>> {
+    let mut ghost = vehicle.clone();
+    let mut slots = Vec::new();
+    let mut slot_tick = tick;
+    let mut exit_travelled = None;
+
+    loop {
+        ghost.move_vehicle(reserved_target(&ghost, target), dt);
+        slot_tick += 1;
+        for tile in covered_tiles(ghost.footprint(RESERVATION_MARGIN)) {
+            if taken.get(&slot_tick).is_some_and(|tiles| tiles.contains_key(&tile)) {
+                return None;
+            }
+            slots.push((slot_tick, tile));
+        }
+
+        if ghost.has_cleared_intersection() {
+            let exit = *exit_travelled.get_or_insert(ghost.distance_travelled);
+            if ghost.distance_travelled - exit >= RESERVATION_EXIT {
+                break;
+            }
+        }
+    }
+
+    Some(slots)
+}
+
+/// Velocity a vehicle holding a reservation at `target` drives at
+fn reserved_target(vehicle: &Vehicle, target: f32) -> f32 {
+    if vehicle.has_cleared_intersection() {
+        SpeedTier::Medium.velocity()
+    } else {
+        target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::intersection::tests::drive;
+
+    #[test]
+    fn keeps_vehicles_apart_and_lets_every_one_through() {
+        for seed in 1..=2 {
+            drive("reservation", seed, false);
+        }
+    }
+}
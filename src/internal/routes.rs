@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::config::RoadConfig;
+use super::vehicles::{Direction, Route, TurnDirection};
+
+/// One row of the route table: the turns allowed from an inbound lane and
+/// the lane each turn leaves the intersection on. Read from the `[[routes]]`
+/// tables of the config, with lanes numbered from 1 as drawn beside the road.
+/// Straight on always keeps the lane.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaneRoutes {
+    /// Direction the traffic in this lane travels
+    pub direction: Direction,
+    pub lane: i32,
+    #[serde(default)]
+    pub left: Option<i32>,
+    #[serde(default)]
+    pub straight: bool,
+    #[serde(default)]
+    pub right: Option<i32>,
+}
+
+impl LaneRoutes {
+    /// Every route from this lane with the lane it ends on, numbered from 0
+    pub fn routes(&self) -> impl Iterator<Item = (Route, i32)> + '_ {
+        let lane = self.lane - 1;
+        [
+            (TurnDirection::Left, self.left.map(|target| target - 1)),
+            (TurnDirection::Straight, self.straight.then_some(lane)),
+            (TurnDirection::Right, self.right.map(|target| target - 1)),
+        ]
+        .into_iter()
+        .filter_map(move |(turn_direction, target)| {
+            let route = Route {
+                direction: self.direction,
+                lane,
+                turn_direction,
+            };
+            target.map(|target| (route, target))
+        })
+    }
+}
+
+/// The table used when the config has none: left turns from the lane next
+/// to the centre line onto the innermost lane, right turns from the outermost
+/// lane onto the outermost lane, and straight on from every other lane, or
+/// from the only lane if there is just one
+pub fn standard_routes(config: &RoadConfig) -> Vec<LaneRoutes> {
+    let lanes = config.roads.lanes_per_direction;
+    let directions = [Direction::North, Direction::South, Direction::East, Direction::West];
+    directions
+        .iter()
+        .flat_map(|&direction| {
+            (0..lanes).map(move |lane_idx| {
+                let lane = config.inbound_start(direction) + lane_idx;
+                let turn = match config.centre_offset(direction, lane) {
+                    0 if lanes >= 3 => TurnDirection::Left,
+                    offset if offset == lanes - 1 && lanes >= 2 => TurnDirection::Right,
+                    _ => TurnDirection::Straight,
+                };
+                let exit_direction = direction.turned(turn);
+                let target = |offset| Some(config.lane_from_centre(exit_direction, offset) + 1);
+                LaneRoutes {
+                    direction,
+                    lane: lane + 1,
+                    left: if turn == TurnDirection::Left { target(0) } else { None },
+                    straight: turn == TurnDirection::Straight,
+                    right: if turn == TurnDirection::Right { target(lanes - 1) } else { None },
+                }
+            })
+        })
+        .collect()
+}
+
+/// Check that every row names an inbound lane of its direction once, allows
+/// at least one turn, that each turn leads onto a lane of the road it exits
+/// on, and that every direction has a route. Routes may merge onto the same
+/// lane; the controllers keep their paths apart inside the box.
+pub fn validate_routes(config: &RoadConfig) -> Result<(), String> {
+    let lanes = config.roads.lanes_per_direction;
+    let mut listed = HashSet::new();
+
+    for row in &config.routes {
+        let name = format!("{:?} lane {}", row.direction, row.lane);
+        let first = config.inbound_start(row.direction);
+        if !(first..first + lanes).contains(&(row.lane - 1)) {
+            return Err(format!(
+                "{} is not a lane for {:?} traffic (lanes {}-{})",
+                name,
+                row.direction,
+                first + 1,
+                first + lanes
+            ));
+        }
+        if !listed.insert((row.direction, row.lane)) {
+            return Err(format!("{} is listed more than once", name));
+        }
+        if row.routes().next().is_none() {
+            return Err(format!("{} allows no turns", name));
+        }
+
+        for (route, target) in row.routes() {
+            let exit_direction = route.exit_direction();
+            let exit_first = config.inbound_start(exit_direction);
+            if !(exit_first..exit_first + lanes).contains(&target) {
+                return Err(format!(
+                    "{} turns {:?} onto lane {}, which is not a lane for {:?} traffic",
+                    name,
+                    route.turn_direction,
+                    target + 1,
+                    exit_direction
+                ));
+            }
+        }
+    }
+
+    let directions = [Direction::North, Direction::South, Direction::East, Direction::West];
+    for direction in directions {
+        if !config.routes.iter().any(|row| row.direction == direction) {
+            return Err(format!("No route for {:?} traffic", direction));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(direction: Direction, lane: i32) -> LaneRoutes {
+        LaneRoutes {
+            direction,
+            lane,
+            left: None,
+            straight: true,
+            right: None,
+        }
+    }
+
+    /// The default config with `change` made to its route table
+    fn validate_with(change: impl FnOnce(&mut Vec<LaneRoutes>)) -> Result<(), String> {
+        let mut config = RoadConfig::default();
+        change(&mut config.routes);
+        validate_routes(&config)
+    }
+
+    #[test]
+    fn standard_routes_are_valid_for_every_lane_count() {
+        for lanes in 1..=5 {
+            let mut config = RoadConfig::default();
+            config.roads.lanes_per_direction = lanes;
+            config.routes = standard_routes(&config);
+            assert_eq!(config.routes.len(), 4 * lanes as usize);
+            validate_routes(&config).unwrap();
+        }
+    }
+
+    #[test]
+    fn rejects_lanes_of_the_other_direction() {
+        // Lanes 1-3 of the vertical road carry southbound traffic
+        let error = validate_with(|routes| routes[0] = row(Direction::North, 1)).unwrap_err();
+        assert!(error.contains("not a lane for North traffic"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_a_lane_listed_twice() {
+        let error = validate_with(|routes| routes.push(routes[0].clone())).unwrap_err();
+        assert!(error.contains("more than once"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_a_lane_without_turns() {
+        let error = validate_with(|routes| routes[0] = LaneRoutes { straight: false, ..row(Direction::North, 4) }).unwrap_err();
+        assert!(error.contains("allows no turns"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_turns_onto_oncoming_lanes() {
+        let error = validate_with(|routes| {
+            let north = routes.iter_mut().find(|row| row.direction == Direction::North).unwrap();
+            // Northbound traffic turning left heads west, on lanes 1-3
+            north.left = Some(4);
+        })
+        .unwrap_err();
+        assert!(error.contains("not a lane for West traffic"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_a_direction_without_routes() {
+        let error = validate_with(|routes| routes.retain(|row| row.direction != Direction::East)).unwrap_err();
+        assert_eq!(error, "No route for East traffic");
+    }
+}
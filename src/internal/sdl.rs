@@ -12,7 +12,8 @@ pub fn init_sdl() -> (Sdl, Canvas<Window>) {
         .build()
         .unwrap();
 
-    let mut canvas = window.into_canvas().build().unwrap();
+    // Presenting waits for the display, so frames are paced by its refresh rate
+    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
 
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
@@ -0,0 +1,169 @@
+use super::constants::*;
+use super::lifecycle::LifecycleState;
+use super::vehicles::{Direction, Vehicle};
+
+/// Approaches in the order a fixed-time signal serves them
+const ROTATION: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
+/// How a signal picks the next approach to turn green
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PhaseOrder {
+    /// Each approach in turn for `SIGNAL_GREEN` seconds
+    Rotation,
+    /// The approach with the most vehicles queued, until it has none left or
+    /// `SIGNAL_MAX_GREEN` seconds have passed. An approach that has had
+    /// vehicles queued through `SIGNAL_MAX_RED` seconds of red goes first,
+    /// so short approaches are not starved by long ones.
+    LongestQueue,
+}
+
+/// A virtual traffic light giving one approach at a time a green. Between
+/// greens it stays all red until every vehicle let in has left the box, so
+/// only one approach's routes are ever in the box together.
+pub struct Signal {
+    order: PhaseOrder,
+    /// Approach with the green; `None` while all red
+    green: Option<Direction>,
+    last_green: Direction,
+    /// Seconds since the current green or all red began
+    elapsed: f32,
+    /// Seconds each approach, in `ROTATION` order, has been red with vehicles
+    /// queued
+    red_for: [f32; 4],
+}
+
+impl Signal {
+    pub fn new(order: PhaseOrder) -> Self {
+        Signal {
+            order,
+            green: None,
+            last_green: ROTATION[ROTATION.len() - 1],
+            elapsed: 0.0,
+            red_for: [0.0; 4],
+        }
+    }
+
+    /// Advance by `dt` seconds; `box_clear` is whether every vehicle let in
+    /// has left the box
+    pub fn update(&mut self, vehicles: &[Vehicle], box_clear: bool, dt: f32) {
+        self.elapsed += dt;
+        for (direction, red_for) in ROTATION.iter().zip(&mut self.red_for) {
+            *red_for = if self.green != Some(*direction) && queue_length(vehicles, *direction) > 0 {
+                *red_for + dt
+            } else {
+                0.0
+            };
+        }
+        match self.green {
+            Some(direction) => {
+                let finished = match self.order {
+                    PhaseOrder::Rotation => self.elapsed >= SIGNAL_GREEN,
+                    PhaseOrder::LongestQueue => {
+                        self.elapsed >= SIGNAL_MAX_GREEN
+                            || (self.elapsed >= SIGNAL_MIN_GREEN && queue_length(vehicles, direction) == 0)
+                    }
+                };
+                if finished {
+                    self.green = None;
+                    self.elapsed = 0.0;
+                }
+            }
+            None if box_clear && self.elapsed >= SIGNAL_ALL_RED => {
+                let next = self.next_green(vehicles);
+                self.green = Some(next);
+                self.last_green = next;
+                self.elapsed = 0.0;
+            }
+            None => {}
+        }
+    }
+
+    pub fn order(&self) -> PhaseOrder {
+        self.order
+    }
+
+    pub fn is_green(&self, direction: Direction) -> bool {
+        self.green == Some(direction)
+    }
+
+    /// Seconds `direction` has been red with vehicles queued
+    fn red_for(&self, direction: Direction) -> f32 {
+        let index = ROTATION.iter().position(|&other| other == direction).unwrap_or(0);
+        self.red_for[index]
+    }
+
+    /// How long `direction` has been red with vehicles queued once that is
+    /// `SIGNAL_MAX_RED` or more; zero before
+    fn overdue_for(&self, direction: Direction) -> f32 {
+        let red_for = self.red_for(direction);
+        if red_for >= SIGNAL_MAX_RED {
+            red_for
+        } else {
+            0.0
+        }
+    }
+
+    fn next_green(&self, vehicles: &[Vehicle]) -> Direction {
+        let last = ROTATION.iter().position(|&direction| direction == self.last_green).unwrap_or(0);
+        let mut upcoming = (1..=ROTATION.len()).map(|offset| ROTATION[(last + offset) % ROTATION.len()]);
+        match self.order {
+            PhaseOrder::Rotation => upcoming.next().unwrap_or(ROTATION[0]),
+            // An overdue approach goes first, the one kept waiting longest if
+            // several are; otherwise the longest queue. Ties go to the
+            // approach that has waited longest, then to the next one round
+            // from the last green.
+            PhaseOrder::LongestQueue => upcoming
+                .rev()
+                .max_by(|&a, &b| {
+                    self.overdue_for(a)
+                        .total_cmp(&self.overdue_for(b))
+                        .then(queue_length(vehicles, a).cmp(&queue_length(vehicles, b)))
+                        .then(self.red_for(a).total_cmp(&self.red_for(b)))
+                })
+                .unwrap_or(ROTATION[0]),
+        }
+    }
+}
+
+/// Vehicles on the approach travelling in `direction` that have not reached
+/// the box yet
+fn queue_length(vehicles: &[Vehicle], direction: Direction) -> usize {
+    vehicles
+        .iter()
+        .filter(|vehicle| vehicle.route.direction == direction)
+        .filter(|vehicle| matches!(vehicle.state, LifecycleState::Approaching | LifecycleState::Waiting))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::internal::policy::policy_named;
+    use crate::internal::simulation::{Input, Simulation};
+
+    use super::*;
+
+    #[test]
+    fn longest_queue_does_not_starve_short_approaches() {
+        // The north-south approaches hold far fewer vehicles than the
+        // east-west ones, so their queues never look longest
+        let mut simulation = Simulation::new(policy_named("longest-queue").unwrap(), 1);
+        let mut approaching_since: HashMap<u32, f64> = HashMap::new();
+        let mut longest: f64 = 0.0;
+        while simulation.time < 150.0 {
+            if simulation.tick % 15 == 0 {
+                simulation.apply(Input::Spawn(ROTATION[(simulation.tick / 15 % 4) as usize]));
+            }
+            simulation.step();
+            for vehicle in &simulation.vehicles {
+                if vehicle.distance_to_intersection().is_some() {
+                    let since = *approaching_since.entry(vehicle.id).or_insert(simulation.time);
+                    longest = longest.max(simulation.time - since);
+                }
+            }
+        }
+        let bound = (SIGNAL_MAX_RED + 2.0 * SIGNAL_MAX_GREEN) as f64;
+        assert!(longest < bound, "a vehicle took {:.1} s to reach the box", longest);
+    }
+}
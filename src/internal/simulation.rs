@@ -0,0 +1,283 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::close_calls::CloseCallDetector;
+use super::config::config;
+use super::constants::SIMULATION_DT;
+use super::emergency::EmergencyPriority;
+use super::lifecycle::{LifecycleEvent, LifecycleListener, LifecycleState};
+use super::policy::IntersectionPolicy;
+use super::statistics::Statistics;
+use super::vehicles::{Direction, Route, Vehicle, VehicleType};
+
+/// A user action that changes the simulation; recorded so a run can be
+/// replayed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Input {
+    /// Spawn a vehicle travelling this way
+    Spawn(Direction),
+    /// Spawn an emergency vehicle, always a car, on a random approach
+    SpawnEmergency,
+    ToggleRandomSpawning,
+}
+
+/// A vehicle added to the simulation, by the user or at random
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Spawn {
+    pub tick: u64,
+    pub id: u32,
+    pub route: Route,
+    pub vehicle_type: VehicleType,
+    pub emergency: bool,
+}
+
+/// The whole traffic simulation, independent of any window or renderer.
+///
+/// All randomness comes from one RNG seeded at construction, so the same seed
+/// and the same inputs on the same ticks always give the same run.
+pub struct Simulation {
+    pub vehicles: Vec<Vehicle>,
+    rng: ChaCha8Rng,
+    next_id: u32,
+    /// Simulated seconds since the start, worked out from `tick` so it does
+    /// not drift over long runs
+    pub time: f64,
+    /// Steps taken since the start
+    pub tick: u64,
+    /// Vehicles added since the owner last took them
+    pub spawns: Vec<Spawn>,
+    /// Vehicles that left the screen in the last step
+    pub removed: Vec<Vehicle>,
+    policy: Box<dyn IntersectionPolicy>,
+    emergency: EmergencyPriority,
+    pub statistics: Statistics,
+    pub close_calls: CloseCallDetector,
+    pub random_spawning: bool,
+    spawn_timer: f32,
+    listeners: Vec<Box<dyn LifecycleListener>>,
+}
+
+impl Simulation {
+    pub fn new(policy: Box<dyn IntersectionPolicy>, seed: u64) -> Self {
+        Simulation {
+            vehicles: Vec::new(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            next_id: 0,
+            time: 0.0,
+            tick: 0,
+            spawns: Vec::new(),
+            removed: Vec::new(),
+            policy,
+            emergency: EmergencyPriority::new(),
+            statistics: Statistics::new(),
+            close_calls: CloseCallDetector::new(config().close_calls),
+            random_spawning: true,
+            spawn_timer: 0.0,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Hear about every vehicle's lifecycle transitions from now on
+    pub fn subscribe(&mut self, listener: impl LifecycleListener + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// Move the subscribers of `other` over to this simulation
+    pub fn take_listeners(&mut self, other: &mut Simulation) {
+        self.listeners.append(&mut other.listeners);
+    }
+
+    pub fn policy(&self) -> &dyn IntersectionPolicy {
+        self.policy.as_ref()
+    }
+
+    pub fn apply(&mut self, input: Input) {
+        match input {
+            Input::Spawn(direction) => {
+                self.spawn(direction);
+            }
+            Input::SpawnEmergency => {
+                let mut vehicle = Vehicle::random(&mut self.rng);
+                vehicle.set_type(VehicleType::Car);
+                vehicle.emergency = true;
+                self.add(vehicle);
+            }
+            Input::ToggleRandomSpawning => self.random_spawning = !self.random_spawning,
+        }
+    }
+
+    /// Spawn a vehicle on a random lane of the approach travelling in
+    /// `direction`, unless that would put it too close to another vehicle
+    pub fn spawn(&mut self, direction: Direction) -> bool {
+        let vehicle = Vehicle::random_from(direction, &mut self.rng);
+        self.add(vehicle)
+    }
+
+    fn add(&mut self, mut vehicle: Vehicle) -> bool {
+        if !vehicle.has_room(&self.vehicles) {
+            return false;
+        }
+        vehicle.id = self.next_id;
+        vehicle.spawn_tick = self.tick;
+        self.next_id += 1;
+        self.spawns.push(Spawn {
+            tick: self.tick,
+            id: vehicle.id,
+            route: vehicle.route,
+            vehicle_type: vehicle.vehicle_type,
+            emergency: vehicle.emergency,
+        });
+        let event = LifecycleEvent {
+            tick: self.tick,
+            time: self.time,
+            vehicle: vehicle.id,
+            from: None,
+            to: vehicle.state,
+        };
+        publish(&mut self.statistics, &mut self.listeners, event, &vehicle);
+        self.vehicles.push(vehicle);
+        true
+    }
+
+    /// Advance the simulation by one fixed step of `SIMULATION_DT` seconds
+    pub fn step(&mut self) {
+        let dt = SIMULATION_DT;
+        self.tick += 1;
+        self.time = self.tick as f64 * dt as f64;
+
+        // Spawn new vehicles (randomly)
+        let spawning = &config().spawning;
+        self.spawn_timer += dt;
+        if self.spawn_timer >= spawning.interval {
+            self.spawn_timer -= spawning.interval;
+            if self.random_spawning
+                && self.vehicles.len() < spawning.max_vehicles
+                && self.rng.gen_bool(spawning.chance)
+            {
+                let vehicle = Vehicle::random(&mut self.rng);
+                self.add(vehicle);
+            }
+        }
+
+        // Ask the intersection policy how fast each vehicle should go, clear
+        // the way for emergency vehicles, then move them
+        let mut targets = self.policy.update(&self.vehicles, dt);
+        for id in self.emergency.give_way(&self.vehicles, &mut targets, dt) {
+            self.policy.withdraw(id);
+        }
+        for (vehicle, target) in self.vehicles.iter_mut().zip(targets) {
+            vehicle.move_vehicle(target, dt);
+        }
+
+        // Move each vehicle on through its lifecycle
+        let (tick, time) = (self.tick, self.time);
+        let event = |vehicle: &Vehicle, to| LifecycleEvent {
+            tick,
+            time,
+            vehicle: vehicle.id,
+            from: Some(vehicle.state),
+            to,
+        };
+        for vehicle in &mut self.vehicles {
+            let state = vehicle.state.next(LifecycleState::observe(vehicle));
+            if state != vehicle.state {
+                let event = event(vehicle, state);
+                vehicle.state = state;
+                publish(&mut self.statistics, &mut self.listeners, event, vehicle);
+            }
+        }
+
+        // Remove vehicles that are off-screen
+        self.removed.clear();
+        self.vehicles.retain_mut(|vehicle| {
+            let on_screen = vehicle.is_on_screen();
+            if !on_screen {
+                let event = event(vehicle, LifecycleState::Despawned);
+                vehicle.state = LifecycleState::Despawned;
+                publish(&mut self.statistics, &mut self.listeners, event, vehicle);
+                self.removed.push(vehicle.clone());
+            }
+            on_screen
+        });
+
+        let new_close_calls = self.close_calls.update(&self.vehicles, self.time).len();
+        self.statistics.close_calls += new_close_calls as u32;
+    }
+}
+
+/// Tell the statistics and every subscriber about a lifecycle transition
+fn publish(
+    statistics: &mut Statistics,
+    listeners: &mut [Box<dyn LifecycleListener>],
+    event: LifecycleEvent,
+    vehicle: &Vehicle,
+) {
+    statistics.on_lifecycle(&event, vehicle);
+    for listener in listeners {
+        listener.on_lifecycle(&event, vehicle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::policy::policy_named;
+
+    /// Id, position and velocity of a vehicle
+    type State = (u32, f32, f32, f32);
+
+    /// Spawns and final vehicle states of a minute of random traffic with one
+    /// emergency vehicle
+    fn run(seed: u64) -> (Vec<Spawn>, Vec<State>) {
+        let mut simulation = Simulation::new(policy_named("conflict").unwrap(), seed);
+        let mut spawns = Vec::new();
+        while simulation.time < 60.0 {
+            if simulation.tick == 600 {
+                simulation.apply(Input::SpawnEmergency);
+            }
+            simulation.step();
+            spawns.append(&mut simulation.spawns);
+        }
+        let states = simulation
+            .vehicles
+            .iter()
+            .map(|vehicle| (vehicle.id, vehicle.pos_x, vehicle.pos_y, vehicle.velocity))
+            .collect();
+        (spawns, states)
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_run() {
+        let (spawns, states) = run(7);
+        assert!(spawns.len() > 10);
+        assert_eq!(run(7), (spawns, states));
+    }
+
+    #[test]
+    fn different_seeds_give_different_runs() {
+        assert_ne!(run(7).0, run(8).0);
+    }
+
+    #[test]
+    fn seed_plays_out_as_it_always_has() {
+        // Pinned, so a platform whose arithmetic differs fails here rather
+        // than replaying recordings differently. Update together with the
+        // recording format version when a change alters how seeds play out.
+        let (spawns, states) = run(7);
+        let checksum = states.iter().fold(spawns.len() as u64, |sum, &(id, x, y, velocity)| {
+            [id, x.to_bits(), y.to_bits(), velocity.to_bits()]
+                .iter()
+                .fold(sum, |sum, &bits| sum.wrapping_mul(31).wrapping_add(bits as u64))
+        });
+        assert_eq!(checksum, 1128106691905);
+    }
+
+    #[test]
+    fn time_follows_the_tick() {
+        let mut simulation = Simulation::new(policy_named("none").unwrap(), 1);
+        for _ in 0..10_000 {
+            simulation.step();
+        }
+        assert_eq!(simulation.time, 10_000.0 * SIMULATION_DT as f64);
+    }
+}
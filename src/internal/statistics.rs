@@ -0,0 +1,172 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use super::lifecycle::{LifecycleEvent, LifecycleListener, LifecycleState};
+use super::vehicles::Vehicle;
+
+/// Velocity extremes of a vehicle that has left the screen
+pub struct VehicleRecord {
+    pub max_velocity: f32,
+    pub min_velocity: f32,
+}
+
+/// Figures collected over the whole run and shown when it ends
+pub struct Statistics {
+    /// Time at which the front of each vehicle currently in the box entered it
+    entered: HashMap<u32, f64>,
+    /// Time at which each vehicle now waiting before the box stopped
+    waiting_since: HashMap<u32, f64>,
+    /// Time spent waiting so far by each vehicle that has not reached the box
+    waited: HashMap<u32, f32>,
+    /// Total time spent waiting by the vehicles that have reached the box
+    total_wait: f32,
+    vehicles_entered: u32,
+    pub vehicles_passed: u32,
+    pub vehicles: Vec<VehicleRecord>,
+    /// Shortest and longest time for the front of a vehicle other than an
+    /// emergency vehicle to cross the box, in seconds
+    pub min_crossing_time: Option<f32>,
+    pub max_crossing_time: Option<f32>,
+    /// Emergency vehicles through the box and their total crossing time, kept
+    /// apart from the crossing times above
+    pub emergency_passed: u32,
+    total_emergency_crossing: f32,
+    pub max_emergency_crossing_time: Option<f32>,
+    pub close_calls: u32,
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Statistics {
+            entered: HashMap::new(),
+            waiting_since: HashMap::new(),
+            waited: HashMap::new(),
+            total_wait: 0.0,
+            vehicles_entered: 0,
+            vehicles_passed: 0,
+            vehicles: Vec::new(),
+            min_crossing_time: None,
+            max_crossing_time: None,
+            emergency_passed: 0,
+            total_emergency_crossing: 0.0,
+            max_emergency_crossing_time: None,
+            close_calls: 0,
+        }
+    }
+
+    /// Mean time spent waiting before the box by the vehicles that have
+    /// reached it, in seconds
+    pub fn average_wait(&self) -> Option<f32> {
+        (self.vehicles_entered > 0).then(|| self.total_wait / self.vehicles_entered as f32)
+    }
+
+    /// Mean time for the front of an emergency vehicle to cross the box, in
+    /// seconds
+    pub fn average_emergency_crossing_time(&self) -> Option<f32> {
+        (self.emergency_passed > 0).then(|| self.total_emergency_crossing / self.emergency_passed as f32)
+    }
+
+    /// Vehicles through the box per minute over `time` seconds
+    pub fn throughput_per_minute(&self, time: f64) -> f32 {
+        if time > 0.0 {
+            (self.vehicles_passed as f64 * 60.0 / time) as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Lines of text summarising the run
+    pub fn summary(&self) -> Vec<String> {
+        let seconds = |time: Option<f32>| match time {
+            Some(time) => format!("{:.2} s", time),
+            None => "-".to_string(),
+        };
+        let velocity = |velocity: Option<f32>| match velocity {
+            Some(velocity) => format!("{:.1} px/s", velocity),
+            None => "-".to_string(),
+        };
+        let max_velocity = self
+            .vehicles
+            .iter()
+            .map(|record| record.max_velocity)
+            .reduce(f32::max);
+        let min_velocity = self
+            .vehicles
+            .iter()
+            .map(|record| record.min_velocity)
+            .reduce(f32::min);
+
+        vec![
+            format!("Vehicles passed: {}", self.vehicles_passed),
+            format!("Max velocity: {}", velocity(max_velocity)),
+            format!("Min velocity: {}", velocity(min_velocity)),
+            format!("Max crossing time: {}", seconds(self.max_crossing_time)),
+            format!("Min crossing time: {}", seconds(self.min_crossing_time)),
+            format!("Average wait: {}", seconds(self.average_wait())),
+            format!(
+                "Emergency crossing: {} avg, {} max",
+                seconds(self.average_emergency_crossing_time()),
+                seconds(self.max_emergency_crossing_time)
+            ),
+            format!("Close calls: {}", self.close_calls),
+        ]
+    }
+}
+
+/// Times waits before the box and crossings from vehicles entering and
+/// exiting it, and keeps the velocity extremes of each vehicle when it is
+/// despawned
+impl LifecycleListener for Statistics {
+    fn on_lifecycle(&mut self, event: &LifecycleEvent, vehicle: &Vehicle) {
+        if event.from == Some(LifecycleState::Waiting) {
+            if let Some(since) = self.waiting_since.remove(&event.vehicle) {
+                *self.waited.entry(event.vehicle).or_insert(0.0) += (event.time - since) as f32;
+            }
+        }
+
+        match event.to {
+            LifecycleState::Waiting => {
+                self.waiting_since.insert(event.vehicle, event.time);
+            }
+            LifecycleState::Entering | LifecycleState::InIntersection => {
+                if let Entry::Vacant(entry) = self.entered.entry(event.vehicle) {
+                    entry.insert(event.time);
+                    self.total_wait += self.waited.remove(&event.vehicle).unwrap_or(0.0);
+                    self.vehicles_entered += 1;
+                }
+            }
+            LifecycleState::Exiting => {
+                if let Some(entered) = self.entered.remove(&event.vehicle) {
+                    let crossing = (event.time - entered) as f32;
+                    self.vehicles_passed += 1;
+                    if vehicle.emergency {
+                        self.emergency_passed += 1;
+                        self.total_emergency_crossing += crossing;
+                        self.max_emergency_crossing_time =
+                            Some(self.max_emergency_crossing_time.map_or(crossing, |max| max.max(crossing)));
+                    } else {
+                        self.min_crossing_time =
+                            Some(self.min_crossing_time.map_or(crossing, |min| min.min(crossing)));
+                        self.max_crossing_time =
+                            Some(self.max_crossing_time.map_or(crossing, |max| max.max(crossing)));
+                    }
+                }
+            }
+            LifecycleState::Despawned => {
+                self.entered.remove(&event.vehicle);
+                self.waiting_since.remove(&event.vehicle);
+                self.waited.remove(&event.vehicle);
+                self.vehicles.push(VehicleRecord {
+                    max_velocity: vehicle.max_velocity,
+                    min_velocity: vehicle.min_velocity,
+                });
+            }
+            LifecycleState::Approaching => {}
+        }
+    }
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
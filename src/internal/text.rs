@@ -0,0 +1,118 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{Canvas, Texture, TextureCreator},
+    rwops::RWops,
+    ttf::{Font, Sdl2TtfContext},
+    video::{Window, WindowContext},
+};
+
+use super::constants::*;
+
+/// DejaVu Sans, built in so text works on machines without any font files
+const EMBEDDED_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+
+/// Where text is rendered from: a font file given with `--font`, or the
+/// embedded font
+#[derive(Clone, Debug)]
+pub enum FontSource {
+    File(String),
+    Embedded,
+}
+
+impl FontSource {
+    /// The font at `path` if one is given, otherwise the embedded font;
+    /// fails if it cannot be loaded
+    pub fn choose(ttf: &Sdl2TtfContext, path: Option<String>) -> Result<FontSource, String> {
+        let source = path.map_or(FontSource::Embedded, FontSource::File);
+        source.load(ttf, 12)?;
+        Ok(source)
+    }
+
+    fn load<'ttf>(&self, ttf: &'ttf Sdl2TtfContext, size: u16) -> Result<Font<'ttf, 'static>, String> {
+        match self {
+            FontSource::File(path) => ttf
+                .load_font(path, size)
+                .map_err(|e| format!("Cannot load font {}: {}", path, e)),
+            FontSource::Embedded => RWops::from_bytes(EMBEDDED_FONT)
+                .and_then(|data| ttf.load_font_from_rwops(data, size))
+                .map_err(|e| format!("Cannot load the embedded font: {}", e)),
+        }
+    }
+}
+
+/// Fonts, one per size, and the textures of rendered strings for one
+/// renderer. Text that is drawn every frame, like lane numbers, is rendered
+/// once and reused; the textures are dropped when there are more than
+/// `TEXT_CACHE_LIMIT` of them, so changing text can't grow it forever. Text
+/// that changes every frame should use `uncached` instead.
+pub struct TextCache<'a> {
+    ttf: &'a Sdl2TtfContext,
+    source: FontSource,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    fonts: HashMap<u16, Font<'a, 'static>>,
+    textures: HashMap<(String, u16, Color), Texture<'a>>,
+}
+
+impl<'a> TextCache<'a> {
+    pub fn new(
+        ttf: &'a Sdl2TtfContext,
+        source: FontSource,
+        texture_creator: &'a TextureCreator<WindowContext>,
+    ) -> Self {
+        TextCache {
+            ttf,
+            source,
+            texture_creator,
+            fonts: HashMap::new(),
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Texture of `text` rendered at `size` points in `color`
+    pub fn texture(&mut self, text: &str, size: u16, color: Color) -> Result<&Texture<'a>, String> {
+        let key = (text.to_string(), size, color);
+        if !self.textures.contains_key(&key) {
+            if self.textures.len() >= TEXT_CACHE_LIMIT {
+                self.textures.clear();
+            }
+            let texture = self.uncached(text, size, color)?;
+            self.textures.insert(key.clone(), texture);
+        }
+        Ok(&self.textures[&key])
+    }
+
+    /// Texture of `text` rendered at `size` points in `color`, without keeping
+    /// it, for text that is unlikely to be drawn again
+    pub fn uncached(&mut self, text: &str, size: u16, color: Color) -> Result<Texture<'a>, String> {
+        let font = match self.fonts.entry(size) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(self.source.load(self.ttf, size)?),
+        };
+        let surface = font.render(text).blended(color).map_err(|e| e.to_string())?;
+        self.texture_creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Draw `text` with its top left corner at (`x`, `y`), stretched to `size`
+    /// if given and at its natural size otherwise
+    pub fn draw(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        text: &str,
+        font_size: u16,
+        color: Color,
+        (x, y): (i32, i32),
+        size: Option<(u32, u32)>,
+    ) -> Result<(), String> {
+        let texture = self.texture(text, font_size, color)?;
+        let (width, height) = size.unwrap_or_else(|| {
+            let query = texture.query();
+            (query.width, query.height)
+        });
+        canvas.copy(texture, None, Some(Rect::new(x, y, width, height)))
+    }
+}
@@ -1,281 +1,475 @@
-use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window};
-use super::lanes::Direction;
+use super::config::{config, RoadConfig};
 use super::constants::*;
+use super::lifecycle::LifecycleState;
+use super::geometry::{oriented_rect, polygon_bounds, polygon_distance, polygons_overlap};
+use super::paths::Path;
+use super::routes::LaneRoutes;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
 
-#[derive(Clone, Copy, PartialEq)]
+/// Direction of travel along a road
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Direction {
+    /// Direction of travel after making `turn`
+    pub fn turned(self, turn: TurnDirection) -> Direction {
+        match (turn, self) {
+            (TurnDirection::Straight, direction) => direction,
+            (TurnDirection::Left, Direction::North) | (TurnDirection::Right, Direction::South) => Direction::West,
+            (TurnDirection::Left, Direction::South) | (TurnDirection::Right, Direction::North) => Direction::East,
+            (TurnDirection::Left, Direction::East) | (TurnDirection::Right, Direction::West) => Direction::North,
+            (TurnDirection::Left, Direction::West) | (TurnDirection::Right, Direction::East) => Direction::South,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
 pub enum TurnDirection {
     Left,
     Straight,
     Right,
 }
 
+/// The inbound lane a vehicle entered on and the turn it takes there
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Route {
+    pub direction: Direction,
+    pub lane: i32,
+    pub turn_direction: TurnDirection,
+}
+
+impl Route {
+    /// Every route in the route table
+    pub fn all() -> Vec<Route> {
+        config()
+            .routes
+            .iter()
+            .flat_map(|row| row.routes().map(|(route, _)| route))
+            .collect()
+    }
+
+    /// Lane the route leaves the intersection on
+    pub fn target_lane(&self) -> i32 {
+        config()
+            .routes
+            .iter()
+            .flat_map(LaneRoutes::routes)
+            .find(|&(route, _)| route == *self)
+            .map(|(_, target)| target)
+            .expect("route is not in the route table")
+    }
+
+    /// Direction of travel after the intersection
+    pub fn exit_direction(&self) -> Direction {
+        self.direction.turned(self.turn_direction)
+    }
+}
+
+/// Cruising speeds a vehicle can be told to hold
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpeedTier {
+    Slow,
+    Medium,
+    Fast,
+}
+
+impl SpeedTier {
+    pub fn velocity(self) -> f32 {
+        match self {
+            SpeedTier::Slow => config().speeds.slow,
+            SpeedTier::Medium => config().speeds.medium,
+            SpeedTier::Fast => config().speeds.fast,
+        }
+    }
+}
+
+/// Kinds of vehicle, each with its own body and performance
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum VehicleType {
+    Car,
+    Truck,
+    Bus,
+    Motorcycle,
+}
+
+/// Body size and performance of a vehicle type, in pixels and pixels per second
+#[derive(Clone, Copy, Debug)]
+pub struct VehicleProfile {
+    /// Along the direction of travel
+    pub length: f32,
+    pub width: f32,
+    pub top_speed: f32,
+    pub acceleration: f32,
+    pub deceleration: f32,
+}
+
+impl VehicleType {
+    pub const ALL: [VehicleType; 4] = [
+        VehicleType::Car,
+        VehicleType::Truck,
+        VehicleType::Bus,
+        VehicleType::Motorcycle,
+    ];
+
+    pub fn profile(self) -> VehicleProfile {
+        self.profile_in(config())
+    }
+
+    /// Profile under `config`, which need not be installed yet. Sizes are
+    /// relative to the configured car and top speeds follow the speed tiers.
+    pub fn profile_in(self, config: &RoadConfig) -> VehicleProfile {
+        let (car, speeds) = (&config.vehicle, &config.speeds);
+        match self {
+            VehicleType::Car => VehicleProfile {
+                length: car.length,
+                width: car.width,
+                top_speed: speeds.fast,
+                acceleration: MAX_ACCELERATION,
+                deceleration: MAX_DECELERATION,
+            },
+            VehicleType::Truck => VehicleProfile {
+                length: car.length * 2.0,
+                width: car.width * 1.2,
+                top_speed: speeds.medium,
+                acceleration: MAX_ACCELERATION * 0.5,
+                deceleration: MAX_DECELERATION * 0.6,
+            },
+            VehicleType::Bus => VehicleProfile {
+                length: car.length * 2.4,
+                width: car.width * 1.2,
+                top_speed: speeds.medium,
+                acceleration: MAX_ACCELERATION * 0.6,
+                deceleration: MAX_DECELERATION * 0.7,
+            },
+            VehicleType::Motorcycle => VehicleProfile {
+                length: car.length * 0.7,
+                width: car.width * 0.45,
+                top_speed: speeds.fast,
+                acceleration: MAX_ACCELERATION * 1.5,
+                deceleration: MAX_DECELERATION * 1.1,
+            },
+        }
+    }
+
+    /// Longest and widest body of any type under `config`, as (length, width)
+    pub fn largest_body(config: &RoadConfig) -> (f32, f32) {
+        VehicleType::ALL.iter().fold((0.0, 0.0), |(length, width), vehicle_type| {
+            let profile = vehicle_type.profile_in(config);
+            (length.max(profile.length), width.max(profile.width))
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct Vehicle {
-    pub pos_x: i32,
-    pub pos_y: i32,
+    /// Unique within a run, in spawn order
+    pub id: u32,
+    /// Tick the vehicle was added to the simulation on
+    pub spawn_tick: u64,
+    pub state: LifecycleState,
+    pub pos_x: f32,
+    pub pos_y: f32,
+    /// Direction of travel in radians, in screen coordinates (0 is east, y down)
+    pub heading: f32,
+    // Pose before the last step, for interpolating between steps when drawing
+    pub prev_pos_x: f32,
+    pub prev_pos_y: f32,
+    pub prev_heading: f32,
     pub direction: Direction,
     pub lane: i32,
     pub turn_direction: TurnDirection,
+    /// Whether the vehicle is on the arc of its turn
     pub turning: bool,
+    pub route: Route,
+    pub path: Path,
+    pub vehicle_type: VehicleType,
+    /// Index into `CAR_SPRITES` of the model drawn for a car
+    pub model: usize,
+    /// Has right of way through the box; see `EmergencyPriority`
+    pub emergency: bool,
+    pub distance_travelled: f32,
+    /// Body size; length is along the heading
+    pub length: f32,
+    pub width: f32,
+    // Kinematics, in pixels per second
+    pub velocity: f32,
+    pub target_velocity: f32,
+    pub max_acceleration: f32,
+    pub max_deceleration: f32,
+    /// Fastest the vehicle's type can go; higher targets are capped to it
+    pub top_speed: f32,
+    // Extremes of `velocity` over the vehicle's life
+    pub max_velocity: f32,
+    pub min_velocity: f32,
 }
 
 impl Vehicle {
     pub fn new(direction: Direction, lane: i32, turn_direction: TurnDirection) -> Self {
-        let (pos_x, pos_y) = Self::get_start_position(direction, lane);
-        
-        Vehicle {
-            pos_x,
-            pos_y,
+        let route = Route {
+            direction,
+            lane,
+            turn_direction,
+        };
+        let mut vehicle = Vehicle {
+            // Assigned by the simulation when the vehicle is added to it
+            id: 0,
+            spawn_tick: 0,
+            state: LifecycleState::Approaching,
+            pos_x: 0.0,
+            pos_y: 0.0,
+            heading: 0.0,
+            prev_pos_x: 0.0,
+            prev_pos_y: 0.0,
+            prev_heading: 0.0,
             direction,
             lane,
             turn_direction,
             turning: false,
-        }
-    }
+            route,
+            path: Path::for_route(route),
+            vehicle_type: VehicleType::Car,
+            model: 0,
+            emergency: false,
+            distance_travelled: 0.0,
+            length: config().vehicle.length,
+            width: config().vehicle.width,
+            velocity: SpeedTier::Medium.velocity(),
+            target_velocity: SpeedTier::Medium.velocity(),
+            max_acceleration: MAX_ACCELERATION,
+            max_deceleration: MAX_DECELERATION,
+            top_speed: SpeedTier::Fast.velocity(),
+            max_velocity: SpeedTier::Medium.velocity(),
+            min_velocity: SpeedTier::Medium.velocity(),
+        };
 
-    fn get_start_position(direction: Direction, lane: i32) -> (i32, i32) {
-        // Convert lane index (0-5) to actual position
-        let lane_offset = lane * LANE_WIDTH + (LANE_WIDTH / 2);
-        
-        match direction {
-            Direction::North => {
-                // For North, lanes go from left to right (1-6)
-                let x = (WINDOW_WIDTH as i32 / 2) - (ROAD_VERTICAL_WIDTH as i32 / 2) + lane_offset;
-                let y = WINDOW_HEIGHT as i32;
-                (x, y)
-            },
-            Direction::South => {
-                // For South, lanes go from left to right (1-6)
-                let x = (WINDOW_WIDTH as i32 / 2) - (ROAD_VERTICAL_WIDTH as i32 / 2) + lane_offset;
-                let y = 0;
-                (x, y)
-            },
-            Direction::East => {
-                // For East, lanes go from top to bottom (1-6)
-                let x = 0;
-                let y = (WINDOW_HEIGHT as i32 / 2) - (ROAD_VERTICAL_WIDTH as i32 / 2) + lane_offset;
-                (x, y)
-            },
-            Direction::West => {
-                // For West, lanes go from top to bottom (1-6)
-                let x = WINDOW_WIDTH as i32;
-                let y = (WINDOW_HEIGHT as i32 / 2) - (ROAD_VERTICAL_WIDTH as i32 / 2) + lane_offset;
-                (x, y)
-            }
-        }
+        let pose = vehicle.path.pose_at(0.0);
+        vehicle.pos_x = pose.x;
+        vehicle.pos_y = pose.y;
+        vehicle.heading = pose.heading;
+        vehicle.prev_pos_x = pose.x;
+        vehicle.prev_pos_y = pose.y;
+        vehicle.prev_heading = pose.heading;
+        vehicle
     }
 
-    pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn random(rng: &mut impl Rng) -> Self {
         let directions = [Direction::North, Direction::South, Direction::East, Direction::West];
         let direction = directions[rng.gen_range(0..4)];
-        
-        // Get valid lanes and their turn directions based on direction
-        let (lane, turn_direction) = match direction {
-            Direction::East => {
-                // East: Only Lanes 4,5,6 (inbound)
-                let lane_idx = rng.gen_range(0..LANES_PER_SIDE);
-                let lane = EAST_INBOUND_START + lane_idx;
-                let turn_direction = match lane_idx {
-                    0 => TurnDirection::Left,     // Lane 4 -> North 4
-                    1 => TurnDirection::Straight, // Lane 5 -> East 5
-                    2 => TurnDirection::Right,    // Lane 6 -> South 1
-                    _ => unreachable!()
-                };
-                (lane, turn_direction)
-            },
-            Direction::South => {
-                // South: Only Lanes 1,2,3 (inbound)
-                let lane_idx = rng.gen_range(0..LANES_PER_SIDE);
-                let lane = SOUTH_INBOUND_START + lane_idx;
-                let turn_direction = match lane_idx {
-                    0 => TurnDirection::Right,    // Lane 1 -> West 1
-                    1 => TurnDirection::Straight, // Lane 2 -> South 2
-                    2 => TurnDirection::Left,     // Lane 3 -> East 4
-                    _ => unreachable!()
-                };
-                (lane, turn_direction)
-            },
-            Direction::West => {
-                // West: Only Lanes 1,2,3 (inbound)
-                let lane_idx = rng.gen_range(0..LANES_PER_SIDE);
-                let lane = WEST_INBOUND_START + lane_idx;
-                let turn_direction = match lane_idx {
-                    0 => TurnDirection::Right,    // Lane 1 -> North 6
-                    1 => TurnDirection::Straight, // Lane 2 -> West 2
-                    2 => TurnDirection::Left,     // Lane 3 -> South 3
-                    _ => unreachable!()
-                };
-                (lane, turn_direction)
-            },
-            Direction::North => {
-                // North: Only Lanes 4,5,6 (inbound)
-                let lane_idx = rng.gen_range(0..LANES_PER_SIDE);
-                let lane = NORTH_INBOUND_START + lane_idx;
-                let turn_direction = match lane_idx {
-                    0 => TurnDirection::Left,     // Lane 4 -> West 3
-                    1 => TurnDirection::Straight, // Lane 5 -> North 5
-                    2 => TurnDirection::Right,    // Lane 6 -> East 6
-                    _ => unreachable!()
-                };
-                (lane, turn_direction)
-            },
+
+        Self::random_from(direction, rng)
+    }
+
+    /// Vehicle on a random inbound lane of the approach travelling in
+    /// `direction`, taking a random one of the turns allowed from that lane
+    pub fn random_from(direction: Direction, rng: &mut impl Rng) -> Self {
+        let rows: Vec<&LaneRoutes> = config()
+            .routes
+            .iter()
+            .filter(|row| row.direction == direction)
+            .collect();
+        let routes: Vec<Route> = rows[rng.gen_range(0..rows.len())]
+            .routes()
+            .map(|(route, _)| route)
+            .collect();
+        // Only draw for the turn on shared lanes
+        let route = match routes.len() {
+            1 => routes[0],
+            count => routes[rng.gen_range(0..count)],
         };
 
-        Vehicle::new(direction, lane, turn_direction)
+        let mut vehicle = Vehicle::new(route.direction, route.lane, route.turn_direction);
+        vehicle.set_type(config().spawning.mix.pick(rng));
+        vehicle.model = rng.gen_range(0..CAR_SPRITES.len());
+        vehicle
     }
 
-    fn get_target_lane(&self) -> i32 {
-        match (self.direction, self.turn_direction) {
-            // From East (Lanes 4,5,6)
-            (Direction::East, TurnDirection::Left) => 3,     // Lane 4 -> North 4
-            (Direction::East, TurnDirection::Straight) => 4, // Lane 5 -> East 5
-            (Direction::East, TurnDirection::Right) => 0,    // Lane 6 -> South 1
-            
-            // From South (Lanes 1,2,3)
-            (Direction::South, TurnDirection::Right) => 0,   // Lane 1 -> West 1
-            (Direction::South, TurnDirection::Straight) => 1, // Lane 2 -> South 2
-            (Direction::South, TurnDirection::Left) => 3,    // Lane 3 -> East 4
-            
-            // From West (Lanes 1,2,3)
-            (Direction::West, TurnDirection::Right) => 5,    // Lane 1 -> North 6
-            (Direction::West, TurnDirection::Straight) => 1, // Lane 2 -> West 2
-            (Direction::West, TurnDirection::Left) => 2,     // Lane 3 -> South 3
-            
-            // From North (Lanes 4,5,6)
-            (Direction::North, TurnDirection::Left) => 2,    // Lane 4 -> West 3
-            (Direction::North, TurnDirection::Straight) => 4, // Lane 5 -> North 5
-            (Direction::North, TurnDirection::Right) => 5,   // Lane 6 -> East 6
-        }
+    /// Give the vehicle the body and performance of `vehicle_type`
+    pub fn set_type(&mut self, vehicle_type: VehicleType) {
+        let profile = vehicle_type.profile();
+        self.vehicle_type = vehicle_type;
+        self.length = profile.length;
+        self.width = profile.width;
+        self.top_speed = profile.top_speed;
+        self.max_acceleration = profile.acceleration;
+        self.max_deceleration = profile.deceleration;
+        self.velocity = self.velocity.min(profile.top_speed);
+        self.target_velocity = self.velocity;
+        self.max_velocity = self.velocity;
+        self.min_velocity = self.velocity;
     }
 
-    fn get_turn_offset(&self) -> (i32, i32) {
-        let offset = LANE_WIDTH;
-        match (self.direction, self.turn_direction) {
-            // From North
-            (Direction::North, TurnDirection::Left) => (-offset, 0),
-            (Direction::North, TurnDirection::Right) => (offset, 0),
-            
-            // From South
-            (Direction::South, TurnDirection::Left) => (offset, 0),
-            (Direction::South, TurnDirection::Right) => (-offset, 0),
-            
-            // From East
-            (Direction::East, TurnDirection::Left) => (0, -offset),
-            (Direction::East, TurnDirection::Right) => (0, offset),
-            
-            // From West
-            (Direction::West, TurnDirection::Left) => (0, offset),
-            (Direction::West, TurnDirection::Right) => (0, -offset),
-            
-            // Straight
-            (_, TurnDirection::Straight) => (0, 0),
-        }
+    /// Whether the vehicle can be added without landing on top of, or too close
+    /// behind, a vehicle that is still near the spawn point
+    pub fn has_room(&self, vehicles: &[Vehicle]) -> bool {
+        vehicles.iter().all(|other| {
+            let overlaps = polygons_overlap(&self.footprint(0.0), &other.footprint(0.0));
+
+            !overlaps && self.gap_to(other).is_none_or(|gap| gap >= config().spawning.gap)
+        })
     }
 
-    fn get_lane_center(&self) -> (i32, i32) {
-        let lane_offset = self.lane * LANE_WIDTH + (LANE_WIDTH / 2);
-        let (offset_x, offset_y) = self.get_turn_offset();
-        
-        match self.direction {
-            Direction::North => {
-                let x = (WINDOW_WIDTH as i32 / 2) - (ROAD_VERTICAL_WIDTH as i32 / 2) + lane_offset;
-                let y = (WINDOW_HEIGHT as i32 / 2) - (ROAD_VERTICAL_WIDTH as i32 / 2);
-                (x + offset_x, y + offset_y)
-            },
-            Direction::South => {
-                let x = (WINDOW_WIDTH as i32 / 2) + (ROAD_VERTICAL_WIDTH as i32 / 2) - lane_offset - LANE_WIDTH;
-                let y = (WINDOW_HEIGHT as i32 / 2) + (ROAD_VERTICAL_WIDTH as i32 / 2);
-                (x + offset_x, y + offset_y)
-            },
-            Direction::East => {
-                let x = (WINDOW_WIDTH as i32 / 2) + (ROAD_HORIZONTAL_WIDTH as i32 / 2);
-                let y = (WINDOW_HEIGHT as i32 / 2) - (ROAD_HORIZONTAL_WIDTH as i32 / 2) + lane_offset;
-                (x + offset_x, y + offset_y)
-            },
-            Direction::West => {
-                let x = (WINDOW_WIDTH as i32 / 2) - (ROAD_HORIZONTAL_WIDTH as i32 / 2);
-                let y = (WINDOW_HEIGHT as i32 / 2) + (ROAD_HORIZONTAL_WIDTH as i32 / 2) - lane_offset - LANE_WIDTH;
-                (x + offset_x, y + offset_y)
-            },
+    /// Accelerate or brake towards `target_velocity` within the vehicle's limits,
+    /// then advance along the path by the new velocity for `dt` seconds
+    pub fn move_vehicle(&mut self, target_velocity: f32, dt: f32) {
+        self.prev_pos_x = self.pos_x;
+        self.prev_pos_y = self.pos_y;
+        self.prev_heading = self.heading;
+        self.target_velocity = target_velocity.clamp(0.0, self.top_speed);
+        self.velocity = if self.target_velocity > self.velocity {
+            (self.velocity + self.max_acceleration * dt).min(self.target_velocity)
+        } else {
+            (self.velocity - self.max_deceleration * dt).max(self.target_velocity)
+        };
+        self.max_velocity = self.max_velocity.max(self.velocity);
+        self.min_velocity = self.min_velocity.min(self.velocity);
+
+        self.distance_travelled += self.velocity * dt;
+        let pose = self.path.pose_at(self.distance_travelled);
+        self.pos_x = pose.x;
+        self.pos_y = pose.y;
+        self.heading = pose.heading;
+
+        if let Some((turn_start, turn_end)) = self.path.turn_span() {
+            self.turning = self.distance_travelled >= turn_start && self.distance_travelled < turn_end;
+
+            // Off the arc and onto the target lane
+            if self.distance_travelled >= turn_end && self.direction == self.route.direction {
+                self.direction = self.route.exit_direction();
+                self.lane = self.path.exit_lane;
+            }
         }
     }
 
-    fn should_turn(&self) -> bool {
-        if self.turning {
-            return false;
-        }
+    /// Pose `alpha` (0-1) of the way from the previous step to the current one,
+    /// as (x, y, heading)
+    pub fn interpolated_pose(&self, alpha: f32) -> (f32, f32, f32) {
+        // Turn the short way round when the heading wraps past +/- pi
+        let turn = (self.heading - self.prev_heading + PI).rem_euclid(2.0 * PI) - PI;
+        (
+            self.prev_pos_x + (self.pos_x - self.prev_pos_x) * alpha,
+            self.prev_pos_y + (self.pos_y - self.prev_pos_y) * alpha,
+            self.prev_heading + turn * alpha,
+        )
+    }
 
-        let (lane_center_x, lane_center_y) = self.get_lane_center();
+    /// Whether the vehicle has been through the intersection and left it
+    pub fn has_cleared_intersection(&self) -> bool {
+        self.distance_to_intersection().is_none() && !self.in_intersection()
+    }
 
-        match self.direction {
-            Direction::North => self.pos_y <= lane_center_y,
-            Direction::South => self.pos_y >= lane_center_y,
-            Direction::East => self.pos_x >= lane_center_x,
-            Direction::West => self.pos_x <= lane_center_x,
-        }
+    /// Distance needed to come to a stop from the current velocity
+    pub fn braking_distance(&self) -> f32 {
+        self.velocity * self.velocity / (2.0 * self.max_deceleration)
     }
 
-    fn get_new_direction(&self) -> Direction {
-        match (self.direction, self.turn_direction) {
-            (Direction::North, TurnDirection::Left) => Direction::West,
-            (Direction::North, TurnDirection::Right) => Direction::East,
-            (Direction::South, TurnDirection::Left) => Direction::East,
-            (Direction::South, TurnDirection::Right) => Direction::West,
-            (Direction::East, TurnDirection::Left) => Direction::North,
-            (Direction::East, TurnDirection::Right) => Direction::South,
-            (Direction::West, TurnDirection::Left) => Direction::South,
-            (Direction::West, TurnDirection::Right) => Direction::North,
-            (dir, TurnDirection::Straight) => dir,
-        }
+    /// Fastest velocity from which the vehicle can still stop within `distance`
+    pub fn stopping_velocity(&self, distance: f32) -> f32 {
+        (2.0 * self.max_deceleration * distance.max(0.0)).sqrt()
+    }
+
+    /// Corners of the vehicle's body, rotated to its heading and grown by
+    /// `margin` on every side
+    pub fn footprint(&self, margin: f32) -> [(f32, f32); 4] {
+        oriented_rect(
+            self.pos_x,
+            self.pos_y,
+            self.heading,
+            self.length / 2.0 + margin,
+            self.width / 2.0 + margin,
+        )
+    }
+
+    /// Axis-aligned bounds of the vehicle's body as (left, top, right, bottom)
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        polygon_bounds(&self.footprint(0.0))
     }
 
-    pub fn move_vehicle(&mut self) {
-        let speed = VEHICLE_SPEED;
+    pub fn is_on_screen(&self) -> bool {
+        self.pos_x >= -50.0
+            && self.pos_x <= WINDOW_WIDTH as f32 + 50.0
+            && self.pos_y >= -50.0
+            && self.pos_y <= WINDOW_HEIGHT as f32 + 50.0
+    }
+
+    /// Whether any part of the vehicle is inside the intersection box
+    pub fn in_intersection(&self) -> bool {
+        let (left, top, right, bottom) = self.bounds();
+        let (west, east, north, south) = config().intersection();
+        right > west && left < east && bottom > north && top < south
+    }
 
-        if self.should_turn() && !self.turning {
-            self.turning = true;
-            let (lane_center_x, lane_center_y) = self.get_lane_center();
-            
-            // Set position to lane center for the turn
+    /// Distance from the front of the vehicle to the edge of the intersection,
+    /// or `None` once the vehicle has reached it
+    pub fn distance_to_intersection(&self) -> Option<f32> {
+        let (left, top, right, bottom) = self.bounds();
+        let (west, east, north, south) = config().intersection();
+        let distance = match self.direction {
+            Direction::North => top - south,
+            Direction::South => north - bottom,
+            Direction::East => west - right,
+            Direction::West => left - east,
+        };
+
+        if distance >= 0.0 {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    /// Distance from the front of this vehicle to the back of `other` if `other`
+    /// is ahead of it on the same route or in the same lane
+    pub fn gap_to(&self, other: &Vehicle) -> Option<f32> {
+        let ahead = if self.route == other.route {
+            // Measured along the path so it still holds through the turn
+            other.distance_travelled - self.distance_travelled
+        } else if self.direction == other.direction && self.lane == other.lane {
             match self.direction {
-                Direction::North => self.pos_y = lane_center_y,
-                Direction::South => self.pos_y = lane_center_y,
-                Direction::East => self.pos_x = lane_center_x,
-                Direction::West => self.pos_x = lane_center_x,
+                Direction::North => self.pos_y - other.pos_y,
+                Direction::South => other.pos_y - self.pos_y,
+                Direction::East => other.pos_x - self.pos_x,
+                Direction::West => self.pos_x - other.pos_x,
             }
-            
-            // Change direction and assign new lane
-            self.direction = self.get_new_direction();
-            self.lane = self.get_target_lane();
+        } else {
+            return None;
+        };
+
+        if ahead <= 0.0 {
+            return None;
         }
+        // Bodies are aligned with the path, so each takes up half its length
+        let gap = ahead - (self.length + other.length) / 2.0;
 
-        // Move in current direction
-        match self.direction {
-            Direction::North => self.pos_y -= speed,
-            Direction::South => self.pos_y += speed,
-            Direction::East => self.pos_x += speed,
-            Direction::West => self.pos_x -= speed,
+        // Round a turn the path is longer than the way between the bodies, so
+        // the gap is capped by how far apart they really are
+        let around_turn = self.route == other.route
+            && self.path.turn_span().is_some_and(|(start, end)| {
+                self.distance_travelled - self.length / 2.0 < end
+                    && other.distance_travelled + other.length / 2.0 > start
+            });
+        if around_turn {
+            Some(gap.min(polygon_distance(&self.footprint(0.0), &other.footprint(0.0))))
+        } else {
+            Some(gap)
         }
     }
+}
 
-    pub fn render(&self, canvas: &mut Canvas<Window>) {
-        let rect = Rect::new(
-            self.pos_x - (VEHICLE_WIDTH as i32 / 2),
-            self.pos_y - (VEHICLE_HEIGHT as i32 / 2),
-            VEHICLE_WIDTH,
-            VEHICLE_HEIGHT,
-        );
-
-        let color = match self.turn_direction {
-            TurnDirection::Left => Color::RGB(255, 100, 100),    // Red
-            TurnDirection::Straight => Color::RGB(100, 255, 100), // Green
-            TurnDirection::Right => Color::RGB(100, 100, 255),   // Blue
-        };
+/// X coordinate of the centre of a lane on the vertical road (numbered left to right)
+pub fn vertical_lane_center(lane: i32) -> f32 {
+    let config = config();
+    config.vertical_lane_edge(lane) + config.roads.lane_width / 2.0
+}
 
-        canvas.set_draw_color(color);
-        canvas.fill_rect(rect).unwrap_or_else(|e| {
-            eprintln!("Error rendering vehicle: {}", e);
-        });
-    }
+/// Y coordinate of the centre of a lane on the horizontal road (numbered top to bottom)
+pub fn horizontal_lane_center(lane: i32) -> f32 {
+    let config = config();
+    config.horizontal_lane_edge(lane) + config.roads.lane_width / 2.0
 }
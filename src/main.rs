@@ -1,22 +1,165 @@
 use internal::{
-    environment::draw_env,
+    config::RoadConfig,
+    constants::{
+        EXPORT_EVERY, MAX_FRAME_RATE, MAX_FRAME_TIME, SCRUB_SECONDS, SIMULATION_DT, SIMULATION_SPEEDS, TICKS_PER_SECOND,
+        TIMELINE_HEIGHT, WINDOW_HEIGHT, WINDOW_WIDTH,
+    },
+    debug::DebugOverlay,
+    export::TrajectoryExporter,
+    hud::Hud,
+    lifecycle::LifecycleEvent,
+    recording::{Recording, Replay},
+    render::{draw_simulation, draw_timeline, show_statistics, Sprites},
     sdl::init_sdl,
-    vehicles::Vehicle,
+    text::{FontSource, TextCache},
+    policy::{policy_named, POLICIES},
+    simulation::{Input, Simulation},
+    vehicles::{Direction, Vehicle},
 };
-use rand::Rng;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 mod internal;
 
 fn main() {
+    check_args();
+
+    // `--replay <file>` runs a recorded session again, with the seed,
+    // policy and configuration it was recorded with
+    let mut replay = arg_value("--replay").map(|path| match Recording::load(&path) {
+        Ok(recording) => Replay::new(recording),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    });
+
+    // `--config <file>` loads the road layout; it has to be in place before
+    // anything below derives geometry from it
+    if let Some(replay) = &replay {
+        replay.recording.config.clone().install();
+    } else if let Some(path) = arg_value("--config") {
+        match RoadConfig::load(&path) {
+            Ok(config) => config.install(),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // `--seed <n>` replays a previous run; otherwise pick a fresh seed
+    let seed = match (&replay, arg_value("--seed")) {
+        (Some(replay), _) => replay.recording.seed,
+        (None, Some(seed)) => seed.parse().unwrap_or_else(|_| {
+            eprintln!("--seed expects a non-negative integer, got {:?}", seed);
+            std::process::exit(2);
+        }),
+        (None, None) => rand::random(),
+    };
+    println!("Seed: {}", seed);
+    let mut simulation = match &mut replay {
+        Some(replay) => replay.restart(),
+        None => {
+            // `--policy <name>` picks the intersection policy; reservations are the default
+            let name = arg_value("--policy").unwrap_or_else(|| POLICIES[0].to_string());
+            let policy = policy_named(&name).unwrap_or_else(|| {
+                eprintln!("Unknown policy {:?}, expected one of: {}", name, POLICIES.join(", "));
+                std::process::exit(2);
+            });
+            Simulation::new(policy, seed)
+        }
+    };
+
+    // `--log-events` prints every vehicle's lifecycle transitions and every
+    // close call
+    let log_events = has_flag("--log-events");
+    if log_events {
+        simulation.subscribe(|event: &LifecycleEvent, _: &Vehicle| {
+            let from = event.from.map_or("spawned".to_string(), |from| format!("{:?}", from));
+            println!("[{:.2} s] vehicle {}: {} -> {:?}", event.time, event.vehicle, from, event.to);
+        });
+    }
+
+    // `--record <file>` saves the seed, configuration, inputs and spawns when
+    // the run ends
+    let mut recording = arg_value("--record")
+        .filter(|_| replay.is_none())
+        .map(|path| (path, Recording::start(seed, &simulation)));
+
+    // `--export <file.csv|file.ndjson>` writes vehicle trajectories every
+    // `--export-every <ticks>` and a summary of each vehicle that leaves.
+    // Seeking in a replay skips and repeats ticks, so replays are not exported.
+    if replay.is_some() && has_flag("--export") {
+        eprintln!("--export cannot be used with --replay; export the run while it is recorded instead");
+        std::process::exit(2);
+    }
+    let every = match arg_value("--export-every") {
+        Some(every) => every.parse().unwrap_or_else(|_| {
+            eprintln!("--export-every expects a number of ticks, got {:?}", every);
+            std::process::exit(2);
+        }),
+        None => EXPORT_EVERY,
+    };
+    let mut exporter = arg_value("--export").map(|path| {
+        TrajectoryExporter::create(&path, every).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        })
+    });
+
+    // `--headless <seconds>` runs without a window and prints the statistics
+    if let Some(seconds) = arg_value("--headless") {
+        let seconds: f64 = seconds.parse().unwrap_or_else(|_| {
+            eprintln!("--headless expects a number of seconds, got {:?}", seconds);
+            std::process::exit(2);
+        });
+        while simulation.time < seconds {
+            let seen_close_calls = simulation.close_calls.events.len();
+            match &mut replay {
+                Some(replay) if replay.finished(&simulation) => break,
+                Some(replay) => replay.step(&mut simulation),
+                None => simulation.step(),
+            }
+            if log_events {
+                log_close_calls(&simulation, seen_close_calls);
+            }
+            export(&mut exporter, &simulation);
+            take_spawns(&mut recording, &mut simulation);
+        }
+        save_recording(recording, &mut simulation);
+        finish_export(exporter);
+        for line in simulation.statistics.summary() {
+            println!("{}", line);
+        }
+        return;
+    }
+
     let (sdl_ctx, mut canvas) = init_sdl();
     let mut event_pump = sdl_ctx.event_pump().unwrap();
-    let mut rng = rand::thread_rng();
-    let mut vehicles = Vec::new();
-    let mut frame_count = 0;
+    let texture_creator = canvas.texture_creator();
+    let sprites = Sprites::load(&texture_creator);
+    // `--font <file>` renders text in that font instead of the built-in one
+    let ttf_context = sdl2::ttf::init().unwrap_or_else(|e| {
+        eprintln!("Cannot start the font renderer: {}", e);
+        std::process::exit(1);
+    });
+    let font = FontSource::choose(&ttf_context, arg_value("--font")).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let mut text = TextCache::new(&ttf_context, font.clone(), &texture_creator);
+    let mut debug_overlay = DebugOverlay::new();
+    let mut hud = Hud::new();
+
+    // Physics always advances in steps of SIMULATION_DT; real time scaled by
+    // the simulation speed builds up in the accumulator until a step is due
+    let mut simulation_speed = 1.0;
+    let mut paused = false;
+    let mut accumulator = 0.0;
+    let mut last_frame = Instant::now();
+    let scrub_ticks = (SCRUB_SECONDS * TICKS_PER_SECOND as f32) as u64;
 
     'running: loop {
         // Handle events
@@ -27,40 +170,223 @@ fn main() {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if debug_overlay.handle_key(keycode) {
+                        continue;
+                    }
+                    match keycode {
+                        Keycode::Space => paused = !paused,
+                        Keycode::Num1 | Keycode::Num2 | Keycode::Num3 | Keycode::Num4 => {
+                            let index = match keycode {
+                                Keycode::Num1 => 0,
+                                Keycode::Num2 => 1,
+                                Keycode::Num3 => 2,
+                                _ => 3,
+                            };
+                            simulation_speed = SIMULATION_SPEEDS[index];
+                            paused = false;
+                        }
+                        _ => {}
+                    }
+
+                    if let Some(replay) = &mut replay {
+                        // Period and comma step one tick and pause; the arrow
+                        // keys, Home and End jump through the recording
+                        let tick = simulation.tick;
+                        let target = match keycode {
+                            Keycode::Period => Some(tick + 1),
+                            Keycode::Comma => Some(tick.saturating_sub(1)),
+                            Keycode::Right => Some(tick + scrub_ticks),
+                            Keycode::Left => Some(tick.saturating_sub(scrub_ticks)),
+                            Keycode::Home => Some(0),
+                            Keycode::End => Some(replay.recording.ticks),
+                            _ => None,
+                        };
+                        if let Some(target) = target {
+                            replay.seek(&mut simulation, target);
+                            accumulator = 0.0;
+                            if matches!(keycode, Keycode::Period | Keycode::Comma) {
+                                paused = true;
+                            }
+                        }
+                        continue;
+                    }
+
+                    // Arrow keys spawn on the approach travelling that way, E
+                    // spawns an emergency vehicle
+                    let input = match keycode {
+                        Keycode::Up => Some(Input::Spawn(Direction::North)),
+                        Keycode::Down => Some(Input::Spawn(Direction::South)),
+                        Keycode::Left => Some(Input::Spawn(Direction::West)),
+                        Keycode::Right => Some(Input::Spawn(Direction::East)),
+                        Keycode::E => Some(Input::SpawnEmergency),
+                        Keycode::R => Some(Input::ToggleRandomSpawning),
+                        _ => None,
+                    };
+                    if let Some(input) = input {
+                        simulation.apply(input);
+                        if let Some((_, recording)) = &mut recording {
+                            recording.inputs.push((simulation.tick, input));
+                        }
+                    }
+                }
+                // Clicking the timeline jumps to that point of a replay
+                Event::MouseButtonDown { x, y, .. } if y >= (WINDOW_HEIGHT - TIMELINE_HEIGHT) as i32 => {
+                    if let Some(replay) = &mut replay {
+                        let fraction = x.max(0) as f64 / WINDOW_WIDTH as f64;
+                        let target = (fraction * replay.recording.ticks as f64).round() as u64;
+                        replay.seek(&mut simulation, target);
+                        accumulator = 0.0;
+                    }
+                }
                 _ => {}
             }
         }
 
-        // Clear screen
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
+        let now = Instant::now();
+        let frame_time = now.duration_since(last_frame).as_secs_f32().min(MAX_FRAME_TIME);
+        last_frame = now;
+        hud.frame(frame_time);
+        if !paused {
+            accumulator += frame_time * simulation_speed;
+        }
 
-        // Draw environment
-        draw_env(&mut canvas);
+        let seen_close_calls = simulation.close_calls.events.len();
+        while accumulator >= SIMULATION_DT {
+            match &mut replay {
+                Some(replay) if replay.finished(&simulation) => {
+                    paused = true;
+                    accumulator = 0.0;
+                    break;
+                }
+                Some(replay) => replay.step(&mut simulation),
+                None => simulation.step(),
+            }
+            export(&mut exporter, &simulation);
+            accumulator -= SIMULATION_DT;
+        }
+        if log_events {
+            log_close_calls(&simulation, seen_close_calls);
+        }
+        take_spawns(&mut recording, &mut simulation);
 
-        // Spawn new vehicles (randomly)
-        frame_count += 1;
-        if frame_count % 60 == 0 && vehicles.len() < 20 {  // Spawn every ~1 second if less than 20 vehicles
-            if rng.gen_bool(0.3) {  // 30% chance to spawn
-                vehicles.push(Vehicle::random());
+        // While paused, show the last step itself rather than the one before it
+        let alpha = if paused { 1.0 } else { accumulator / SIMULATION_DT };
+        draw_simulation(&mut canvas, &mut text, &simulation, &sprites, alpha);
+        debug_overlay.draw(&mut canvas, &mut text, &simulation, alpha);
+        hud.draw(&mut canvas, &mut text, &simulation, (!paused).then_some(simulation_speed));
+        if let Some(replay) = &replay {
+            draw_timeline(&mut canvas, simulation.tick, replay.recording.ticks);
+        }
+        canvas.present();
+        // Only sleep out what is left of the frame, in case vsync is off
+        let shortest_frame = Duration::from_secs(1) / MAX_FRAME_RATE;
+        if let Some(rest) = shortest_frame.checked_sub(last_frame.elapsed()) {
+            ::std::thread::sleep(rest);
+        }
+    }
+
+    canvas.window_mut().hide();
+    save_recording(recording, &mut simulation);
+    finish_export(exporter);
+    show_statistics(&sdl_ctx, &mut event_pump, &ttf_context, &font, &simulation.statistics);
+}
+
+/// Print the close calls detected since there were `seen` of them
+fn log_close_calls(simulation: &Simulation, seen: usize) {
+    for close_call in &simulation.close_calls.events[seen..] {
+        println!(
+            "Close call between vehicles {} and {} at ({:.0}, {:.0}) after {:.2} s",
+            close_call.vehicles.0, close_call.vehicles.1, close_call.pos_x, close_call.pos_y, close_call.time
+        );
+    }
+}
+
+/// Hand the vehicles spawned since the last call to the recording, or drop
+/// them if the run is not being recorded
+fn take_spawns(recording: &mut Option<(String, Recording)>, simulation: &mut Simulation) {
+    match recording {
+        Some((_, recording)) => recording.update(simulation),
+        None => simulation.spawns.clear(),
+    }
+}
+
+fn save_recording(recording: Option<(String, Recording)>, simulation: &mut Simulation) {
+    if let Some((path, mut recording)) = recording {
+        recording.update(simulation);
+        match recording.save(&path) {
+            Ok(()) => println!("Recorded {} ticks to {}", recording.ticks, path),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+/// Export the last step, giving up on exporting after the first error
+fn export(exporter: &mut Option<TrajectoryExporter>, simulation: &Simulation) {
+    if let Some(writer) = exporter {
+        if let Err(e) = writer.record(simulation) {
+            eprintln!("{}", e);
+            *exporter = None;
+        }
+    }
+}
+
+fn finish_export(exporter: Option<TrajectoryExporter>) {
+    if let Some(Err(e)) = exporter.map(TrajectoryExporter::finish) {
+        eprintln!("{}", e);
+    }
+}
+
+/// Options followed by a value, and options on their own
+const VALUE_OPTIONS: [&str; 9] = [
+    "--replay",
+    "--config",
+    "--seed",
+    "--policy",
+    "--record",
+    "--export",
+    "--export-every",
+    "--headless",
+    "--font",
+];
+const FLAGS: [&str; 1] = ["--log-events"];
+
+/// Exit with an error on any argument that is not a known option, or an
+/// option missing its value
+fn check_args() {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if VALUE_OPTIONS.contains(&arg.as_str()) {
+            if args.next().is_none() {
+                eprintln!("{} expects a value", arg);
+                std::process::exit(2);
             }
+        } else if !FLAGS.contains(&arg.as_str()) {
+            eprintln!(
+                "Unknown argument {:?}, expected one of: {}",
+                arg,
+                VALUE_OPTIONS.iter().chain(&FLAGS).copied().collect::<Vec<_>>().join(", ")
+            );
+            std::process::exit(2);
         }
+    }
+}
 
-        // Update and render vehicles
-        vehicles.retain_mut(|vehicle| {
-            vehicle.move_vehicle();
-            vehicle.render(&mut canvas);
-            
-            // Remove vehicles that are off-screen
-            let x = vehicle.pos_x;
-            let y = vehicle.pos_y;
-            x >= -50
-                && x <= canvas.window().size().0 as i32 + 50
-                && y >= -50
-                && y <= canvas.window().size().1 as i32 + 50
-        });
+/// Whether `name` was given on the command line
+fn has_flag(name: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == name)
+}
 
-        canvas.present();
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+/// Value following `name` on the command line, if given
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
     }
+    None
 }